@@ -0,0 +1,250 @@
+//! Decodes a JWT without verifying it and, given a key set, verifies it, printing why
+//! verification fails along with the token's timing relative to this machine's clock.
+
+use google_jwt_verify::{decode_unverified, Client, Error, OfflineKeyProvider};
+use serde_json::Value;
+use std::env;
+use std::io::{self, Read};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "\
+usage: jwt-inspect [OPTIONS] <TOKEN>
+
+Prints the header and claims of TOKEN, or of the token read from stdin when TOKEN is `-`.
+
+options:
+    --jwks <FILE|URL>     verify the token against a key set file, directory or URL
+    --audience <AUD>      accepted `aud`, required with --jwks; may be repeated
+    --issuer <ISS>        accepted `iss`, defaults to Google's issuers; may be repeated
+    --leeway <SECONDS>    tolerated clock skew when verifying, defaults to 0";
+
+struct Options {
+    token: String,
+    jwks: Option<String>,
+    audiences: Vec<String>,
+    issuers: Vec<String>,
+    leeway: u64,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut token = None;
+    let mut jwks = None;
+    let mut audiences = Vec::new();
+    let mut issuers = Vec::new();
+    let mut leeway = 0;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+        match arg.as_str() {
+            "--jwks" => jwks = Some(value("--jwks")?),
+            "--audience" => audiences.push(value("--audience")?),
+            "--issuer" => issuers.push(value("--issuer")?),
+            "--leeway" => {
+                leeway = value("--leeway")?
+                    .parse()
+                    .map_err(|_| "--leeway must be a number of seconds".to_owned())?
+            }
+            "-h" | "--help" => return Err(String::new()),
+            option if option.starts_with("--") => return Err(format!("unknown option {}", option)),
+            _ if token.is_some() => return Err("more than one token given".to_owned()),
+            _ => token = Some(arg),
+        }
+    }
+    if jwks.is_some() && audiences.is_empty() {
+        return Err("--jwks requires at least one --audience".to_owned());
+    }
+    Ok(Options {
+        token: token.ok_or_else(|| "no token given".to_owned())?,
+        jwks,
+        audiences,
+        issuers,
+        leeway,
+    })
+}
+
+fn read_token(token: String) -> io::Result<String> {
+    if token != "-" {
+        return Ok(token);
+    }
+    let mut token = String::new();
+    io::stdin().read_to_string(&mut token)?;
+    Ok(token.trim().to_owned())
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) if message.is_empty() => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let token = read_token(options.token.clone()).unwrap_or_else(|e| {
+        eprintln!("failed to read the token from stdin: {}", e);
+        process::exit(2);
+    });
+
+    let decoded = decode_unverified(&token).unwrap_or_else(|e| {
+        eprintln!("cannot decode token: {}", describe(&e));
+        process::exit(1);
+    });
+    println!("header: {:#}", decoded.get_header());
+    println!("claims: {:#}", decoded.get_claims());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    print_times(decoded.get_claims(), now);
+
+    let jwks = match options.jwks {
+        Some(ref jwks) => jwks,
+        None => return,
+    };
+    match verify(&token, jwks, &options) {
+        Ok(()) => println!("verification: ok"),
+        Err(message) => {
+            println!("verification failed: {}", message);
+            process::exit(1);
+        }
+    }
+}
+
+fn verify(token: &str, jwks: &str, options: &Options) -> Result<(), String> {
+    let mut builder = Client::builder_with_audiences(&options.audiences)
+        .leeway(Duration::from_secs(options.leeway));
+    if !options.issuers.is_empty() {
+        builder = builder.issuers(&options.issuers);
+    }
+    let result = if jwks.starts_with("https://") || jwks.starts_with("http://") {
+        builder
+            .jwks_url(jwks)
+            .build()
+            .verify_token_with_payload::<Value>(token)
+    } else {
+        let keys = OfflineKeyProvider::from_path(jwks)
+            .map_err(|e| format!("cannot load key set {}: {}", jwks, e))?;
+        builder
+            .custom_key_provider(keys)
+            .build()
+            .verify_token_with_payload::<Value>(token)
+    };
+    result.map(|_| ()).map_err(|e| describe(&e))
+}
+
+fn describe(error: &Error) -> String {
+    let hint = match error {
+        Error::UnknownKeyId(_) => Some("the key set has no such key; it may have been rotated"),
+        Error::RetrieveKeyFailure => Some("the key set could not be downloaded"),
+        Error::InvalidIssuer(_) => Some("pass the expected issuer with --issuer"),
+        Error::Expired | Error::NotYetValid => Some("see the times above"),
+        _ => None,
+    };
+    match (std::error::Error::source(error), hint) {
+        (Some(source), _) => format!("{} ({})", error, source),
+        (None, Some(hint)) => format!("{} ({})", error, hint),
+        (None, None) => error.to_string(),
+    }
+}
+
+fn print_times(claims: &Value, now: u64) {
+    println!("now:        {}", format_timestamp(now));
+    let issued_at = claims["iat"].as_u64();
+    if let Some(issued_at) = issued_at {
+        println!(
+            "issued at:  {}, {}",
+            format_timestamp(issued_at),
+            relative(issued_at, now)
+        );
+    }
+    if let Some(not_before) = claims["nbf"].as_u64() {
+        println!(
+            "not before: {}, {}",
+            format_timestamp(not_before),
+            relative(not_before, now)
+        );
+    }
+    if let Some(expires_at) = claims["exp"].as_u64() {
+        println!(
+            "expires at: {}, {}",
+            format_timestamp(expires_at),
+            relative(expires_at, now)
+        );
+        if let Some(issued_at) = issued_at {
+            let lifetime = expires_at.saturating_sub(issued_at);
+            println!("lifetime:   {}", format_duration(lifetime));
+        }
+        if expires_at <= now {
+            println!(
+                "status:     expired {} ago",
+                format_duration(now - expires_at)
+            );
+        }
+    }
+    // a token cannot have been issued after it was received, so this is clock skew
+    if let Some(issued_at) = issued_at.filter(|&issued_at| issued_at > now) {
+        println!(
+            "clock skew: the issuer's clock is {} ahead of this machine's",
+            format_duration(issued_at - now)
+        );
+    }
+}
+
+fn relative(timestamp: u64, now: u64) -> String {
+    if timestamp > now {
+        format!("in {}", format_duration(timestamp - now))
+    } else {
+        format!("{} ago", format_duration(now - timestamp))
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    let (days, hours, minutes, seconds) = (
+        seconds / 86400,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    );
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, _) => format!("{}m {}s", minutes, seconds),
+        (0, _, _) => format!("{}h {}m {}s", hours, minutes, seconds),
+        _ => format!("{}d {}h {}m", days, hours, minutes),
+    }
+}
+
+/// Formats a Unix timestamp followed by its UTC date and time.
+fn format_timestamp(timestamp: u64) -> String {
+    // days to civil date, from Howard Hinnant's `civil_from_days`
+    let days = (timestamp / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    let seconds = timestamp % 86400;
+    format!(
+        "{} ({:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC)",
+        timestamp,
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
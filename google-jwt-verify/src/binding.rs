@@ -0,0 +1,69 @@
+use crate::algorithm::Algorithm;
+use crate::error::Error;
+use openssl::hash::{hash, MessageDigest};
+use serde_derive::Deserialize;
+
+/// Values an ID token must be bound to, on top of the client's claim checks.
+///
+/// A `nonce` ties the token to the login request that asked for it, so a captured
+/// token cannot be replayed into another login. An `access_token` issued alongside
+/// the ID token is checked against the token's `at_hash` claim.
+#[derive(Clone, Default, Debug)]
+pub struct TokenBinding {
+    nonce: Option<String>,
+    access_token: Option<String>,
+}
+
+impl TokenBinding {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Requires the token's `nonce` claim to equal `nonce`.
+    pub fn nonce(mut self, nonce: &str) -> Self {
+        self.nonce = Some(nonce.to_owned());
+        self
+    }
+    /// Requires the token's `at_hash` claim to match `access_token`.
+    pub fn access_token(mut self, access_token: &str) -> Self {
+        self.access_token = Some(access_token.to_owned());
+        self
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub(crate) struct BindingClaims {
+    nonce: Option<String>,
+    at_hash: Option<String>,
+}
+
+impl BindingClaims {
+    pub fn validate(&self, algorithm: Algorithm, binding: &TokenBinding) -> Result<(), Error> {
+        if let Some(ref expected) = binding.nonce {
+            if self.nonce.as_ref() != Some(expected) {
+                return Err(Error::InvalidNonce);
+            }
+        }
+        if let Some(ref access_token) = binding.access_token {
+            let expected = access_token_hash(algorithm, access_token)?;
+            if self.at_hash.as_ref() != Some(&expected) {
+                return Err(Error::InvalidAccessTokenHash);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `at_hash` of an access token: the left half of its hash, using the hash function
+/// of the ID token's signing algorithm, base64url encoded.
+fn access_token_hash(algorithm: Algorithm, access_token: &str) -> Result<String, Error> {
+    let digest = match algorithm {
+        Algorithm::RS256 | Algorithm::ES256 | Algorithm::HS256 => MessageDigest::sha256(),
+        Algorithm::RS384 | Algorithm::ES384 | Algorithm::HS384 => MessageDigest::sha384(),
+        Algorithm::RS512 | Algorithm::ES512 | Algorithm::HS512 => MessageDigest::sha512(),
+    };
+    let hashed = hash(digest, access_token.as_bytes())?;
+    Ok(base64::encode_config(
+        &hashed[..hashed.len() / 2],
+        base64::URL_SAFE_NO_PAD,
+    ))
+}
@@ -1,65 +1,148 @@
+use crate::algorithm::Algorithm;
+use crate::binding::TokenBinding;
+use crate::clock::Clock;
+use crate::discovery::ProviderMetadata;
 use crate::error::Error;
+use crate::firebase::{firebase_issuer, FirebasePayload, FIREBASE_JWKS_URL};
 #[cfg(feature = "async")]
 use crate::key_provider::AsyncKeyProvider;
-use crate::key_provider::GoogleKeyProvider;
 #[cfg(feature = "blocking")]
 use crate::key_provider::KeyProvider;
+use crate::key_provider::{GoogleKeyProvider, JwksKeyProvider};
 use crate::token::IdPayload;
 use crate::token::Token;
 use crate::unverified_token::UnverifiedToken;
+use crate::validation::Validation;
 use serde::Deserialize;
 
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub type Client = GenericClient<GoogleKeyProvider>;
 
 pub struct GenericClientBuilder<KP> {
-    client_id: String,
+    validation: Validation,
     key_provider: Arc<Mutex<KP>>,
-    check_expiration: bool,
 }
 
 impl<KP: Default> GenericClientBuilder<KP> {
     pub fn new(client_id: &str) -> GenericClientBuilder<KP> {
+        GenericClientBuilder::with_audiences(&[client_id])
+    }
+    /// Accepts tokens issued to any of the given client IDs, e.g. the web, iOS and Android
+    /// clients of the same application.
+    pub fn with_audiences<S: AsRef<str>>(audiences: &[S]) -> GenericClientBuilder<KP> {
         GenericClientBuilder::<KP> {
-            client_id: client_id.to_owned(),
+            validation: Validation::new(audiences),
             key_provider: Arc::new(Mutex::new(KP::default())),
-            check_expiration: true,
         }
     }
 }
 
+impl GenericClientBuilder<JwksKeyProvider> {
+    /// Verifies Firebase Authentication ID tokens of the given Firebase project.
+    pub fn firebase(project_id: &str) -> GenericClientBuilder<JwksKeyProvider> {
+        Self::new(project_id)
+            .issuers(&[firebase_issuer(project_id)])
+            .jwks_url(FIREBASE_JWKS_URL)
+            .algorithms(&[Algorithm::RS256])
+    }
+}
+
 impl<KP> GenericClientBuilder<KP> {
     pub fn custom_key_provider<T>(self, provider: T) -> GenericClientBuilder<T> {
         GenericClientBuilder {
-            client_id: self.client_id,
+            validation: self.validation,
             key_provider: Arc::new(Mutex::new(provider)),
-            check_expiration: self.check_expiration,
         }
     }
+    /// Points the client at an arbitrary JWKS endpoint instead of Google's certificates.
+    pub fn jwks_url(self, url: &str) -> GenericClientBuilder<JwksKeyProvider> {
+        self.custom_key_provider(JwksKeyProvider::new(url))
+    }
+    /// Verifies tokens of an OpenID Connect provider, using its issuer and JWKS endpoint.
+    pub fn openid_provider(
+        mut self,
+        metadata: &ProviderMetadata,
+    ) -> GenericClientBuilder<JwksKeyProvider> {
+        self.validation.set_issuers(&[metadata.get_issuer()]);
+        self.jwks_url(&metadata.get_jwks_uri())
+    }
+    /// Replaces the accepted `iss` values, which default to Google's issuers.
+    pub fn issuers<S: AsRef<str>>(mut self, issuers: &[S]) -> Self {
+        self.validation.set_issuers(issuers);
+        self
+    }
+    /// Restricts the accepted header `alg` values, which default to all supported RSA and
+    /// ECDSA algorithms. `none` and the HMAC algorithms are rejected regardless.
+    pub fn algorithms(mut self, algorithms: &[Algorithm]) -> Self {
+        self.validation.set_algorithms(algorithms);
+        self
+    }
+    pub fn audience(mut self, client_id: &str) -> Self {
+        self.validation.add_audience(client_id);
+        self
+    }
+    /// Additionally requires the `azp` claim to be one of the accepted audiences.
+    pub fn check_authorized_party(mut self) -> Self {
+        self.validation.set_check_authorized_party(true);
+        self
+    }
+    /// Only accepts tokens whose `hd` claim is one of the given Google Workspace domains.
+    /// May be called multiple times to allow several domains.
+    pub fn hosted_domain(mut self, domain: &str) -> Self {
+        self.validation.add_hosted_domain(domain);
+        self
+    }
+    /// Tolerates this much clock skew when checking `exp`, `nbf` and `iat`.
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.validation.set_leeway(leeway);
+        self
+    }
+    /// Rejects tokens whose `nbf` claim lies in the future.
+    pub fn check_not_before(mut self) -> Self {
+        self.validation.set_check_not_before(true);
+        self
+    }
+    /// Rejects tokens issued (`iat`) longer ago than `max_age`, regardless of `exp`.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.validation.set_max_age(max_age);
+        self
+    }
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.validation.set_clock(clock);
+        self
+    }
     pub fn unsafe_ignore_expiration(mut self) -> Self {
-        self.check_expiration = false;
+        self.validation.set_check_expiration(false);
         self
     }
     pub fn build(self) -> GenericClient<KP> {
         GenericClient {
-            client_id: self.client_id,
+            validation: self.validation,
             key_provider: self.key_provider,
-            check_expiration: self.check_expiration,
         }
     }
 }
 
 pub struct GenericClient<T> {
-    client_id: String,
+    validation: Validation,
     key_provider: Arc<Mutex<T>>,
-    check_expiration: bool,
+}
+
+impl GenericClient<JwksKeyProvider> {
+    pub fn firebase_builder(project_id: &str) -> GenericClientBuilder<JwksKeyProvider> {
+        GenericClientBuilder::firebase(project_id)
+    }
 }
 
 impl<KP: Default> GenericClient<KP> {
     pub fn builder(client_id: &str) -> GenericClientBuilder<KP> {
         GenericClientBuilder::<KP>::new(client_id)
     }
+    pub fn builder_with_audiences<S: AsRef<str>>(audiences: &[S]) -> GenericClientBuilder<KP> {
+        GenericClientBuilder::<KP>::with_audiences(audiences)
+    }
     pub fn new(client_id: &str) -> GenericClient<KP> {
         GenericClientBuilder::new(client_id).build()
     }
@@ -71,8 +154,7 @@ impl<KP: KeyProvider> GenericClient<KP> {
     where
         for<'a> P: Deserialize<'a>,
     {
-        let unverified_token =
-            UnverifiedToken::<P>::validate(token_string, self.check_expiration, &self.client_id)?;
+        let unverified_token = UnverifiedToken::<P>::validate(token_string, &self.validation)?;
         unverified_token.verify(&self.key_provider)
     }
 
@@ -83,10 +165,45 @@ impl<KP: KeyProvider> GenericClient<KP> {
     pub fn verify_id_token(&self, token_string: &str) -> Result<Token<IdPayload>, Error> {
         self.verify_token_with_payload(token_string)
     }
+
+    pub fn verify_firebase_token(
+        &self,
+        token_string: &str,
+    ) -> Result<Token<FirebasePayload>, Error> {
+        let token = self.verify_token_with_payload::<FirebasePayload>(token_string)?;
+        self.validation
+            .validate_auth_time(token.get_payload().get_auth_time())?;
+        Ok(token)
+    }
+
+    /// Like `verify_token_with_payload`, additionally checking the token's `nonce` and
+    /// `at_hash` against `binding`.
+    pub fn verify_bound_token_with_payload<P>(
+        &self,
+        token_string: &str,
+        binding: &TokenBinding,
+    ) -> Result<Token<P>, Error>
+    where
+        for<'a> P: Deserialize<'a>,
+    {
+        let unverified_token = UnverifiedToken::<P>::validate(token_string, &self.validation)?;
+        unverified_token.validate_binding(binding)?;
+        unverified_token.verify(&self.key_provider)
+    }
+
+    pub fn verify_bound_id_token(
+        &self,
+        token_string: &str,
+        binding: &TokenBinding,
+    ) -> Result<Token<IdPayload>, Error> {
+        self.verify_bound_token_with_payload(token_string, binding)
+    }
 }
 
+/// The provider is cloned out of the mutex before awaiting it, so that no lock is held
+/// across a key download; providers used here should share their cache between clones.
 #[cfg(feature = "async")]
-impl<KP: AsyncKeyProvider> GenericClient<KP> {
+impl<KP: AsyncKeyProvider + Clone> GenericClient<KP> {
     pub async fn verify_token_with_payload_async<P>(
         &self,
         token_string: &str,
@@ -94,9 +211,9 @@ impl<KP: AsyncKeyProvider> GenericClient<KP> {
     where
         for<'a> P: Deserialize<'a>,
     {
-        let unverified_token =
-            UnverifiedToken::<P>::validate(token_string, self.check_expiration, &self.client_id)?;
-        unverified_token.verify_async(&self.key_provider).await
+        let unverified_token = UnverifiedToken::<P>::validate(token_string, &self.validation)?;
+        let key_provider = self.key_provider.lock().unwrap().clone();
+        unverified_token.verify_async(&key_provider).await
     }
 
     pub async fn verify_token_async(&self, token_string: &str) -> Result<Token<()>, Error> {
@@ -110,4 +227,39 @@ impl<KP: AsyncKeyProvider> GenericClient<KP> {
     ) -> Result<Token<IdPayload>, Error> {
         self.verify_token_with_payload_async(token_string).await
     }
+
+    pub async fn verify_firebase_token_async(
+        &self,
+        token_string: &str,
+    ) -> Result<Token<FirebasePayload>, Error> {
+        let token = self
+            .verify_token_with_payload_async::<FirebasePayload>(token_string)
+            .await?;
+        self.validation
+            .validate_auth_time(token.get_payload().get_auth_time())?;
+        Ok(token)
+    }
+
+    pub async fn verify_bound_token_with_payload_async<P>(
+        &self,
+        token_string: &str,
+        binding: &TokenBinding,
+    ) -> Result<Token<P>, Error>
+    where
+        for<'a> P: Deserialize<'a>,
+    {
+        let unverified_token = UnverifiedToken::<P>::validate(token_string, &self.validation)?;
+        unverified_token.validate_binding(binding)?;
+        let key_provider = self.key_provider.lock().unwrap().clone();
+        unverified_token.verify_async(&key_provider).await
+    }
+
+    pub async fn verify_bound_id_token_async(
+        &self,
+        token_string: &str,
+        binding: &TokenBinding,
+    ) -> Result<Token<IdPayload>, Error> {
+        self.verify_bound_token_with_payload_async(token_string, binding)
+            .await
+    }
 }
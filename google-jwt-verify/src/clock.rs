@@ -0,0 +1,23 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time used when checking `exp`, `nbf` and `iat`.
+/// Tests can supply a fixed clock to exercise expiry without waiting.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    fn timestamp(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
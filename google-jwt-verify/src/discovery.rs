@@ -0,0 +1,62 @@
+use crate::error::Error;
+use serde_derive::Deserialize;
+
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+
+/// The subset of an OpenID Connect provider's discovery document needed to verify its tokens.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ProviderMetadata {
+    issuer: String,
+    jwks_uri: String,
+}
+
+impl ProviderMetadata {
+    pub fn new(issuer: &str, jwks_uri: &str) -> ProviderMetadata {
+        ProviderMetadata {
+            issuer: issuer.to_owned(),
+            jwks_uri: jwks_uri.to_owned(),
+        }
+    }
+    pub fn get_issuer(&self) -> String {
+        self.issuer.clone()
+    }
+    pub fn get_jwks_uri(&self) -> String {
+        self.jwks_uri.clone()
+    }
+
+    fn discovery_url(issuer: &str) -> String {
+        format!("{}{}", issuer.trim_end_matches('/'), DISCOVERY_PATH)
+    }
+
+    fn from_document(issuer: &str, text: &str) -> Result<ProviderMetadata, Error> {
+        let metadata: ProviderMetadata =
+            serde_json::from_str(text).map_err(|_| Error::RetrieveKeyFailure)?;
+        // the discovery document must describe the issuer it was fetched from
+        if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(Error::RetrieveKeyFailure);
+        }
+        Ok(metadata)
+    }
+
+    #[cfg(feature = "blocking")]
+    pub fn discover(issuer: &str) -> Result<ProviderMetadata, Error> {
+        let text = reqwest::blocking::get(&Self::discovery_url(issuer))
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(|_| Error::RetrieveKeyFailure)?;
+        Self::from_document(issuer, &text)
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn discover_async(issuer: &str) -> Result<ProviderMetadata, Error> {
+        let response = reqwest::get(&Self::discovery_url(issuer))
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| Error::RetrieveKeyFailure)?;
+        let text = response
+            .text()
+            .await
+            .map_err(|_| Error::RetrieveKeyFailure)?;
+        Self::from_document(issuer, &text)
+    }
+}
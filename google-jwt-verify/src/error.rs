@@ -1,28 +1,177 @@
 use crate::algorithm::Algorithm;
 use base64::DecodeError;
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
+/// The dot separated parts of a compact JWT.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Segment {
+    Header,
+    Payload,
+    Signature,
+}
+
+#[derive(Debug)]
 pub enum Error {
+    /// The token does not consist of exactly three dot separated segments.
+    MalformedToken,
+    /// A segment is not valid base64url.
+    MalformedSegment(Segment, DecodeError),
+    /// The header is not valid JSON or lacks a required member.
+    InvalidHeader(serde_json::Error),
+    /// The header's `typ` is present but is not `JWT`.
+    InvalidTokenType(String),
+    /// The header's `alg` is `none`, symmetric, unknown or not in the client's allow-list.
+    DisallowedAlgorithm(String),
+    /// The payload does not deserialize into the required claims or the requested payload type.
+    InvalidPayload(serde_json::Error),
+    /// None of the token's `aud` values is an accepted audience.
+    InvalidAudience(Vec<String>),
+    /// The token's `azp` is missing or not an accepted audience.
+    InvalidAuthorizedParty(Option<String>),
+    /// The token's `iss` is not an accepted issuer.
+    InvalidIssuer(String),
+    InvalidHostedDomain,
+    /// The token's `nonce` is missing or differs from the expected one.
+    InvalidNonce,
+    /// The token's `at_hash` is missing or does not match the access token.
+    InvalidAccessTokenHash,
+    /// The claims are inconsistent, e.g. `iat` lies after `exp`.
     InvalidToken,
+    Expired,
+    NotYetValid,
+    /// The user's `auth_time` lies in the future.
+    InvalidAuthTime,
+    /// The key set does not contain the token's `kid`, even after a refresh.
+    UnknownKeyId(String),
+    /// The key selected by `kid` is meant for another algorithm than the header's `alg`,
+    /// e.g. an RSA key for an ES256 token.
+    AlgorithmMismatch(Algorithm),
+    /// The key selected by `kid` has malformed key material.
+    InvalidKey,
+    /// The signature does not match the token's header and payload.
+    InvalidSignature,
+    /// OpenSSL failed while loading the key or checking the signature.
+    Crypto(openssl::error::ErrorStack),
     RetrieveKeyFailure,
     UnsupportedAlgorithm(Algorithm),
-    Expired,
+    /// The tokeninfo endpoint rejected the access token, with its reason.
+    InvalidAccessToken(String),
+    /// The access token was not granted a required scope.
+    MissingScope(String),
+    /// The tokeninfo endpoint could not be reached or answered unexpectedly.
+    IntrospectionFailure,
+    /// The token was revoked before it expired.
+    Revoked,
+    /// The revocation list could not be consulted.
+    RevocationCheckFailure,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MalformedToken => write!(f, "token is not a JWT with three segments"),
+            Error::MalformedSegment(segment, e) => {
+                write!(f, "{:?} segment is not valid base64url: {}", segment, e)
+            }
+            Error::InvalidHeader(e) => write!(f, "invalid token header: {}", e),
+            Error::InvalidTokenType(token_type) => {
+                write!(f, "token type {} is not accepted", token_type)
+            }
+            Error::DisallowedAlgorithm(algorithm) => {
+                write!(f, "algorithm {} is not allowed", algorithm)
+            }
+            Error::InvalidPayload(e) => write!(f, "invalid token payload: {}", e),
+            Error::InvalidAudience(audiences) => {
+                write!(f, "token audience {:?} is not accepted", audiences)
+            }
+            Error::InvalidAuthorizedParty(azp) => {
+                write!(f, "token authorized party {:?} is not accepted", azp)
+            }
+            Error::InvalidIssuer(issuer) => write!(f, "token issuer {} is not accepted", issuer),
+            Error::InvalidHostedDomain => write!(f, "token hosted domain is not accepted"),
+            Error::InvalidNonce => write!(f, "token nonce does not match"),
+            Error::InvalidAccessTokenHash => {
+                write!(f, "token at_hash does not match the access token")
+            }
+            Error::InvalidToken => write!(f, "invalid token"),
+            Error::Expired => write!(f, "token has expired"),
+            Error::NotYetValid => write!(f, "token is not valid yet"),
+            Error::UnknownKeyId(key_id) => write!(f, "no key found for kid {}", key_id),
+            Error::AlgorithmMismatch(algorithm) => {
+                write!(f, "key cannot be used to verify {:?} tokens", algorithm)
+            }
+            Error::InvalidKey => write!(f, "key material is invalid"),
+            Error::InvalidSignature => write!(f, "token signature does not match"),
+            Error::Crypto(e) => write!(f, "signature verification failed: {}", e),
+            Error::InvalidAuthTime => write!(f, "user authenticated in the future"),
+            Error::RetrieveKeyFailure => write!(f, "failed to retrieve the key set"),
+            Error::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "unsupported algorithm {:?}", algorithm)
+            }
+            Error::InvalidAccessToken(reason) => write!(f, "invalid access token: {}", reason),
+            Error::MissingScope(scope) => write!(f, "access token lacks scope {}", scope),
+            Error::IntrospectionFailure => write!(f, "failed to introspect the access token"),
+            Error::Revoked => write!(f, "token has been revoked"),
+            Error::RevocationCheckFailure => {
+                write!(f, "failed to check whether the token is revoked")
+            }
+        }
+    }
 }
 
-impl From<DecodeError> for Error {
-    fn from(_: DecodeError) -> Self {
-        Error::InvalidToken
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::MalformedSegment(_, e) => Some(e),
+            Error::InvalidHeader(e) | Error::InvalidPayload(e) => Some(e),
+            Error::Crypto(e) => Some(e),
+            _ => None,
+        }
     }
 }
 
-impl From<serde_json::Error> for Error {
-    fn from(_: serde_json::Error) -> Self {
-        Error::InvalidToken
+/// Source errors from serde_json and OpenSSL do not implement `PartialEq`, so
+/// they are compared by their message.
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        use Error::*;
+        match (self, other) {
+            (MalformedSegment(a, e), MalformedSegment(b, f)) => a == b && e == f,
+            (InvalidHeader(e), InvalidHeader(f)) | (InvalidPayload(e), InvalidPayload(f)) => {
+                e.to_string() == f.to_string()
+            }
+            (InvalidAudience(a), InvalidAudience(b)) => a == b,
+            (InvalidAuthorizedParty(a), InvalidAuthorizedParty(b)) => a == b,
+            (InvalidIssuer(a), InvalidIssuer(b))
+            | (UnknownKeyId(a), UnknownKeyId(b))
+            | (InvalidTokenType(a), InvalidTokenType(b))
+            | (DisallowedAlgorithm(a), DisallowedAlgorithm(b))
+            | (InvalidAccessToken(a), InvalidAccessToken(b))
+            | (MissingScope(a), MissingScope(b)) => a == b,
+            (Crypto(e), Crypto(f)) => e.to_string() == f.to_string(),
+            (UnsupportedAlgorithm(a), UnsupportedAlgorithm(b))
+            | (AlgorithmMismatch(a), AlgorithmMismatch(b)) => a == b,
+            (MalformedToken, MalformedToken)
+            | (InvalidHostedDomain, InvalidHostedDomain)
+            | (InvalidNonce, InvalidNonce)
+            | (InvalidAccessTokenHash, InvalidAccessTokenHash)
+            | (InvalidToken, InvalidToken)
+            | (Expired, Expired)
+            | (NotYetValid, NotYetValid)
+            | (InvalidAuthTime, InvalidAuthTime)
+            | (InvalidKey, InvalidKey)
+            | (InvalidSignature, InvalidSignature)
+            | (RetrieveKeyFailure, RetrieveKeyFailure)
+            | (IntrospectionFailure, IntrospectionFailure)
+            | (Revoked, Revoked)
+            | (RevocationCheckFailure, RevocationCheckFailure) => true,
+            _ => false,
+        }
     }
 }
 
 impl From<openssl::error::ErrorStack> for Error {
-    fn from(_: openssl::error::ErrorStack) -> Self {
-        Error::InvalidToken
+    fn from(e: openssl::error::ErrorStack) -> Self {
+        Error::Crypto(e)
     }
 }
@@ -0,0 +1,74 @@
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Firebase Authentication signs ID tokens with the `securetoken` service account.
+pub const FIREBASE_JWKS_URL: &str =
+    "https://www.googleapis.com/service_accounts/v1/jwk/securetoken@system.gserviceaccount.com";
+
+/// The `iss` of ID tokens issued for a Firebase project, whose `aud` is the project ID.
+pub fn firebase_issuer(project_id: &str) -> String {
+    format!("https://securetoken.google.com/{}", project_id)
+}
+
+#[derive(Deserialize, Clone)]
+pub struct FirebasePayload {
+    user_id: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: Option<bool>,
+    #[serde(default)]
+    phone_number: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    picture: Option<String>,
+    auth_time: u64,
+    firebase: FirebaseClaims,
+}
+
+#[derive(Deserialize, Clone)]
+struct FirebaseClaims {
+    sign_in_provider: String,
+    #[serde(default)]
+    tenant: Option<String>,
+    #[serde(default)]
+    identities: HashMap<String, Value>,
+}
+
+impl FirebasePayload {
+    /// The Firebase user ID, which is also the token's `sub`.
+    pub fn get_user_id(&self) -> String {
+        self.user_id.clone()
+    }
+    pub fn get_email(&self) -> Option<String> {
+        self.email.clone()
+    }
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified.unwrap_or(false)
+    }
+    pub fn get_phone_number(&self) -> Option<String> {
+        self.phone_number.clone()
+    }
+    pub fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    pub fn get_picture_url(&self) -> Option<String> {
+        self.picture.clone()
+    }
+    pub fn get_auth_time(&self) -> u64 {
+        self.auth_time
+    }
+    /// How the user signed in, e.g. `google.com`, `apple.com`, `password` or `anonymous`.
+    pub fn get_sign_in_provider(&self) -> String {
+        self.firebase.sign_in_provider.clone()
+    }
+    pub fn get_tenant(&self) -> Option<String> {
+        self.firebase.tenant.clone()
+    }
+    /// The user's IDs at each linked sign-in provider, keyed by provider.
+    pub fn get_identities(&self) -> &HashMap<String, Value> {
+        &self.firebase.identities
+    }
+}
@@ -2,6 +2,11 @@ use serde_derive::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub struct Header {
+    /// Kept as a string so that `none` and unknown algorithms can be reported by name.
+    #[serde(rename = "alg")]
+    pub algorithm: String,
+    #[serde(rename = "typ", default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
     #[serde(rename = "kid")]
     pub key_id: String,
 }
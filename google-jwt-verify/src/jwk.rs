@@ -2,31 +2,66 @@ use crate::algorithm::Algorithm;
 use crate::base64_decode;
 use crate::error::Error;
 use openssl::bn::BigNum;
-use openssl::hash::MessageDigest;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
 use openssl::pkey::PKey;
 use openssl::rsa::Rsa;
 use openssl::sign::Verifier;
+use serde::Deserializer;
 use serde_derive::Deserialize;
 
 #[derive(Deserialize, Clone)]
 pub struct JsonWebKeySet {
+    #[serde(deserialize_with = "deserialize_supported_keys")]
     keys: Vec<JsonWebKey>,
 }
 
+/// Providers publish encryption keys and key types this crate cannot use next to
+/// their signing keys, so entries that fail to parse are skipped instead of
+/// rejecting the whole set.
+fn deserialize_supported_keys<'de, D>(deserializer: D) -> Result<Vec<JsonWebKey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let keys: Vec<serde_json::Value> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(keys
+        .into_iter()
+        .filter_map(|key| serde_json::from_value(key).ok())
+        .collect())
+}
+
 impl JsonWebKeySet {
     pub fn get_key(&self, id: &str) -> Option<JsonWebKey> {
         self.keys.iter().find(|key| key.id == id).cloned()
     }
+    pub(crate) fn empty() -> Self {
+        JsonWebKeySet { keys: Vec::new() }
+    }
+    pub(crate) fn extend(&mut self, other: JsonWebKeySet) {
+        self.keys.extend(other.keys);
+    }
 }
 
 #[derive(Deserialize, Clone)]
 pub struct JsonWebKey {
-    #[serde(rename = "alg")]
-    algorithm: Algorithm,
+    #[serde(rename = "alg", default)]
+    algorithm: Option<Algorithm>,
     #[serde(rename = "kid")]
     id: String,
-    n: String,
-    e: String,
+    #[serde(flatten)]
+    parameters: KeyParameters,
+}
+
+/// Key material of a JWK, selected by its `kty` member.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kty")]
+enum KeyParameters {
+    #[serde(rename = "RSA")]
+    Rsa { n: String, e: String },
+    #[serde(rename = "EC")]
+    Ec { crv: String, x: String, y: String },
 }
 
 impl JsonWebKey {
@@ -34,18 +69,138 @@ impl JsonWebKey {
         self.id.clone()
     }
 
-    pub fn verify(&self, body: &[u8], signature: &[u8]) -> Result<(), Error> {
-        match self.algorithm {
+    /// The key's `alg`, or the conventional algorithm for its key type when the
+    /// JWK leaves `alg` out (as Microsoft's key sets do).
+    pub fn get_algorithm(&self) -> Option<Algorithm> {
+        if self.algorithm.is_some() {
+            return self.algorithm;
+        }
+        match self.parameters {
+            KeyParameters::Rsa { .. } => Some(Algorithm::RS256),
+            KeyParameters::Ec { ref crv, .. } => match crv.as_str() {
+                "P-256" => Some(Algorithm::ES256),
+                "P-384" => Some(Algorithm::ES384),
+                "P-521" => Some(Algorithm::ES512),
+                _ => None,
+            },
+        }
+    }
+
+    /// Verifies `signature` with the token's `algorithm`, which must match the key's
+    /// `alg` when the JWK declares one and must fit its key type and curve otherwise.
+    pub fn verify(&self, algorithm: Algorithm, body: &[u8], signature: &[u8]) -> Result<(), Error> {
+        if self.algorithm.is_some_and(|own| own != algorithm) {
+            return Err(Error::AlgorithmMismatch(algorithm));
+        }
+        match algorithm {
             Algorithm::RS256 => {
-                let n = BigNum::from_slice(&base64_decode(&self.n)?)?;
-                let e = BigNum::from_slice(&base64_decode(&self.e)?)?;
-                let key = PKey::from_rsa(Rsa::from_public_components(n, e)?)?;
-                let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
-                verifier.update(body)?;
-                verifier.verify(signature)?;
-                Ok(())
+                self.verify_rsa(algorithm, MessageDigest::sha256(), body, signature)
+            }
+            Algorithm::RS384 => {
+                self.verify_rsa(algorithm, MessageDigest::sha384(), body, signature)
+            }
+            Algorithm::RS512 => {
+                self.verify_rsa(algorithm, MessageDigest::sha512(), body, signature)
             }
-            _ => Err(Error::UnsupportedAlgorithm(self.algorithm)),
+            Algorithm::ES256 => self.verify_ec(
+                algorithm,
+                Nid::X9_62_PRIME256V1,
+                MessageDigest::sha256(),
+                body,
+                signature,
+            ),
+            Algorithm::ES384 => self.verify_ec(
+                algorithm,
+                Nid::SECP384R1,
+                MessageDigest::sha384(),
+                body,
+                signature,
+            ),
+            Algorithm::ES512 => self.verify_ec(
+                algorithm,
+                Nid::SECP521R1,
+                MessageDigest::sha512(),
+                body,
+                signature,
+            ),
+            _ => Err(Error::UnsupportedAlgorithm(algorithm)),
         }
     }
+
+    fn verify_rsa(
+        &self,
+        algorithm: Algorithm,
+        digest: MessageDigest,
+        body: &[u8],
+        signature: &[u8],
+    ) -> Result<(), Error> {
+        let (n, e) = match self.parameters {
+            KeyParameters::Rsa { ref n, ref e } => (n, e),
+            _ => return Err(Error::AlgorithmMismatch(algorithm)),
+        };
+        let n = decode_key_part(n)?;
+        let e = decode_key_part(e)?;
+        let key = PKey::from_rsa(Rsa::from_public_components(n, e)?)?;
+        let mut verifier = Verifier::new(digest, &key)?;
+        verifier.update(body)?;
+        if verifier.verify(signature)? {
+            Ok(())
+        } else {
+            Err(Error::InvalidSignature)
+        }
+    }
+
+    fn verify_ec(
+        &self,
+        algorithm: Algorithm,
+        curve: Nid,
+        digest: MessageDigest,
+        body: &[u8],
+        signature: &[u8],
+    ) -> Result<(), Error> {
+        let (crv, x, y) = match self.parameters {
+            KeyParameters::Ec {
+                ref crv,
+                ref x,
+                ref y,
+            } => (crv, x, y),
+            _ => return Err(Error::AlgorithmMismatch(algorithm)),
+        };
+        if curve_name(curve) != Some(crv.as_str()) {
+            return Err(Error::AlgorithmMismatch(algorithm));
+        }
+        let group = EcGroup::from_curve_name(curve)?;
+        let x = decode_key_part(x)?;
+        let y = decode_key_part(y)?;
+        let key = EcKey::from_public_key_affine_coordinates(&group, &x, &y)?;
+
+        // JWS carries ECDSA signatures as the fixed-width concatenation r || s
+        // rather than DER, so the two halves are split out by hand.
+        let width = group.degree().div_ceil(8) as usize;
+        if signature.len() != 2 * width {
+            return Err(Error::InvalidSignature);
+        }
+        let (r, s) = signature.split_at(width);
+        let signature =
+            EcdsaSig::from_private_components(BigNum::from_slice(r)?, BigNum::from_slice(s)?)?;
+        if signature.verify(&hash(digest, body)?, &key)? {
+            Ok(())
+        } else {
+            Err(Error::InvalidSignature)
+        }
+    }
+}
+
+fn decode_key_part(value: &str) -> Result<BigNum, Error> {
+    let bytes = base64_decode(value).map_err(|_| Error::InvalidKey)?;
+    Ok(BigNum::from_slice(&bytes)?)
+}
+
+fn curve_name(curve: Nid) -> Option<&'static str> {
+    match curve {
+        Nid::X9_62_PRIME256V1 => Some("P-256"),
+        Nid::SECP384R1 => Some("P-384"),
+        Nid::SECP521R1 => Some("P-521"),
+        _ => None,
+    }
 }
@@ -4,89 +4,313 @@ use crate::jwk::JsonWebKeySet;
 use async_trait::async_trait;
 use headers::{Header, HeaderMap};
 use reqwest::header::CACHE_CONTROL;
-use std::time::Instant;
+#[cfg(feature = "async")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+#[cfg(feature = "async")]
+use tokio::sync::Mutex as AsyncMutex;
+
+pub const GOOGLE_CERT_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
 
-const GOOGLE_CERT_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+/// How long to wait before downloading again after a failed refresh.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Minimum time between downloads forced by a token carrying an unknown `kid`, so
+/// that bogus key IDs cannot be used to hammer the JWKS endpoint.
+const FORCED_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
 #[cfg(feature = "blocking")]
 pub trait KeyProvider {
+    #[allow(clippy::result_unit_err)]
     fn get_key(&mut self, key_id: &str) -> Result<Option<JsonWebKey>, ()>;
 }
 
+/// Async key lookup through a shared reference, so that a provider can serve
+/// concurrent verifications without an outer lock.
 #[cfg(feature = "async")]
 #[async_trait]
-pub trait AsyncKeyProvider {
-    async fn get_key_async(&mut self, key_id: &str) -> Result<Option<JsonWebKey>, ()>;
+pub trait AsyncKeyProvider: Send + Sync {
+    async fn get_key_async(&self, key_id: &str) -> Result<Option<JsonWebKey>, ()>;
+}
+
+/// Lets a client hold a provider chosen at runtime, e.g. Google's endpoint in
+/// production and an in-memory key set in tests.
+#[cfg(feature = "async")]
+#[async_trait]
+impl<T: AsyncKeyProvider + ?Sized> AsyncKeyProvider for Box<T> {
+    async fn get_key_async(&self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+        (**self).get_key_async(key_id).await
+    }
+}
+
+/// Rate limits the downloads forced by tokens carrying an unknown `kid`, to at most one
+/// per minute, so that bogus key IDs cannot be used to hammer a JWKS endpoint.
+/// Clones share the same limit.
+#[derive(Clone, Default)]
+pub struct ForcedRefreshThrottle {
+    last_forced_refresh: Arc<Mutex<Option<Instant>>>,
+}
+
+impl ForcedRefreshThrottle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claims the forced refresh slot, returning false while it is rate limited.
+    pub fn try_start(&self) -> bool {
+        let mut last_forced_refresh = self.last_forced_refresh.lock().unwrap();
+        let now = Instant::now();
+        if last_forced_refresh.is_some_and(|last| now < last + FORCED_REFRESH_INTERVAL) {
+            return false;
+        }
+        *last_forced_refresh = Some(now);
+        true
+    }
 }
 
+/// Downloads and caches a JSON Web Key Set from a JWKS endpoint, honouring the
+/// endpoint's Cache-Control `max-age`.
+///
+/// Keys are served from a snapshot behind a `RwLock` that is never held across a
+/// download. The async provider refreshes the set in the background once three
+/// quarters of its lifetime have passed, concurrent cache misses share a single
+/// download, and the last good set keeps being served while downloads fail.
+/// A token signed with a key that is not in the cached set forces an early
+/// download, at most once per minute, to pick up rotated keys.
+/// Clones share the same cache.
 #[derive(Clone)]
-pub struct GoogleKeyProvider {
-    cached: Option<JsonWebKeySet>,
+pub struct JwksKeyProvider {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    url: String,
+    cache: RwLock<KeyCache>,
+    throttle: ForcedRefreshThrottle,
+    #[cfg(feature = "async")]
+    refreshing: AtomicBool,
+    #[cfg(feature = "async")]
+    refresh_lock: AsyncMutex<()>,
+}
+
+#[derive(Clone)]
+struct KeyCache {
+    keys: Option<Arc<JsonWebKeySet>>,
     expiration_time: Instant,
+    refresh_time: Instant,
+    next_attempt: Instant,
 }
 
-impl Default for GoogleKeyProvider {
+impl KeyCache {
+    fn fresh_keys(&self, now: Instant) -> Option<&Arc<JsonWebKeySet>> {
+        self.keys.as_ref().filter(|_| now < self.expiration_time)
+    }
+    fn get_key(&self, key_id: &str) -> Option<JsonWebKey> {
+        self.keys.as_ref().and_then(|keys| keys.get_key(key_id))
+    }
+}
+
+/// Google's key provider is a JWKS provider pointed at Google's certificate endpoint.
+pub type GoogleKeyProvider = JwksKeyProvider;
+
+impl Default for JwksKeyProvider {
     fn default() -> Self {
-        Self {
-            cached: None,
-            expiration_time: Instant::now(),
-        }
+        Self::new(GOOGLE_CERT_URL)
     }
 }
 
-impl GoogleKeyProvider {
-    fn process_response(&mut self, headers: &HeaderMap, text: &str) -> Result<&JsonWebKeySet, ()> {
-        let mut expiration_time = None;
-        let x = headers.get_all(CACHE_CONTROL);
-        if let Ok(cache_header) = headers::CacheControl::decode(&mut x.iter()) {
-            if let Some(max_age) = cache_header.max_age() {
-                expiration_time = Some(Instant::now() + max_age);
-            }
-        }
-        let key_set = serde_json::from_str(&text).map_err(|_| ())?;
-        if let Some(expiration_time) = expiration_time {
-            self.cached = Some(key_set);
-            self.expiration_time = expiration_time;
+impl JwksKeyProvider {
+    pub fn new(url: &str) -> Self {
+        let now = Instant::now();
+        Self {
+            shared: Arc::new(Shared {
+                url: url.to_owned(),
+                cache: RwLock::new(KeyCache {
+                    keys: None,
+                    expiration_time: now,
+                    refresh_time: now,
+                    next_attempt: now,
+                }),
+                throttle: ForcedRefreshThrottle::new(),
+                #[cfg(feature = "async")]
+                refreshing: AtomicBool::new(false),
+                #[cfg(feature = "async")]
+                refresh_lock: AsyncMutex::new(()),
+            }),
         }
-        Ok(self.cached.as_ref().unwrap())
     }
+    pub fn get_url(&self) -> &str {
+        &self.shared.url
+    }
+
+    fn snapshot(&self) -> KeyCache {
+        self.shared.cache.read().unwrap().clone()
+    }
+
+    fn process_response(&self, headers: &HeaderMap, text: &str) -> Result<Arc<JsonWebKeySet>, ()> {
+        let now = Instant::now();
+        let max_age = cache_max_age(headers);
+        let key_set: Arc<JsonWebKeySet> = Arc::new(serde_json::from_str(text).map_err(|_| ())?);
+        let mut cache = self.shared.cache.write().unwrap();
+        cache.keys = Some(key_set.clone());
+        cache.expiration_time = now + max_age;
+        cache.refresh_time = now + max_age * 3 / 4;
+        cache.next_attempt = now;
+        Ok(key_set)
+    }
+
+    /// Records a failed download and falls back to the last good key set, if any.
+    fn download_failed(&self) -> Result<Arc<JsonWebKeySet>, ()> {
+        let mut cache = self.shared.cache.write().unwrap();
+        cache.next_attempt = Instant::now() + RETRY_INTERVAL;
+        cache.keys.clone().ok_or(())
+    }
+
+    /// Claims the forced refresh slot, returning false while it is rate limited.
+    fn start_forced_refresh(&self) -> bool {
+        Instant::now() >= self.snapshot().next_attempt && self.shared.throttle.try_start()
+    }
+
     #[cfg(feature = "blocking")]
-    pub fn download_keys(&mut self) -> Result<&JsonWebKeySet, ()> {
-        let result = reqwest::blocking::get(GOOGLE_CERT_URL).map_err(|_| ())?;
+    #[allow(clippy::result_unit_err)]
+    pub fn download_keys(&self) -> Result<Arc<JsonWebKeySet>, ()> {
+        let result = reqwest::blocking::get(&self.shared.url).map_err(|_| ())?;
         self.process_response(&result.headers().clone(), &result.text().map_err(|_| ())?)
     }
+
     #[cfg(feature = "async")]
-    async fn download_keys_async(&mut self) -> Result<&JsonWebKeySet, ()> {
-        let result = reqwest::get(GOOGLE_CERT_URL).await.map_err(|_| ())?;
+    async fn download_keys_async(&self) -> Result<Arc<JsonWebKeySet>, ()> {
+        let result = reqwest::get(&self.shared.url).await.map_err(|_| ())?;
         self.process_response(
             &result.headers().clone(),
             &result.text().await.map_err(|_| ())?,
         )
     }
+
+    /// Downloads the key set unless another caller refreshed it while this one
+    /// waited for the refresh lock.
+    #[cfg(feature = "async")]
+    async fn refresh_async(&self, force: bool) -> Result<Arc<JsonWebKeySet>, ()> {
+        let _guard = self.shared.refresh_lock.lock().await;
+        let cache = self.snapshot();
+        let now = Instant::now();
+        if !force {
+            if let Some(keys) = cache.fresh_keys(now) {
+                return Ok(keys.clone());
+            }
+        }
+        if now < cache.next_attempt {
+            return cache.keys.ok_or(());
+        }
+        match self.download_keys_async().await {
+            Ok(keys) => Ok(keys),
+            Err(_) => self.download_failed(),
+        }
+    }
+
+    /// Downloads the key set early because a token referenced a key it does not
+    /// contain. Callers queue on the refresh lock, so a rotation seen by many
+    /// concurrent requests results in a single download.
+    #[cfg(feature = "async")]
+    async fn refresh_for_unknown_key_async(&self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+        let _guard = self.shared.refresh_lock.lock().await;
+        if let Some(key) = self.snapshot().get_key(key_id) {
+            return Ok(Some(key));
+        }
+        if !self.start_forced_refresh() {
+            return Ok(None);
+        }
+        let keys = match self.download_keys_async().await {
+            Ok(keys) => keys,
+            Err(_) => self.download_failed()?,
+        };
+        Ok(keys.get_key(key_id))
+    }
+
+    #[cfg(feature = "async")]
+    fn spawn_background_refresh(&self) {
+        if self.shared.refreshing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let provider = self.clone();
+        tokio::spawn(async move {
+            let _ = provider.refresh_async(true).await;
+            provider.shared.refreshing.store(false, Ordering::Release);
+        });
+    }
+}
+
+/// The Cache-Control `max-age` of a JWKS response, or zero when it has none.
+fn cache_max_age(headers: &HeaderMap) -> Duration {
+    let values = headers.get_all(CACHE_CONTROL);
+    headers::CacheControl::decode(&mut values.iter())
+        .ok()
+        .and_then(|cache_control| cache_control.max_age())
+        .unwrap_or_else(|| Duration::from_secs(0))
+}
+
+/// Downloads a key set document without caching it, returning the raw JSON together
+/// with how long it may be cached. This lets applications keep the key set in a cache
+/// shared between processes.
+#[cfg(feature = "async")]
+#[allow(clippy::result_unit_err)]
+pub async fn download_key_set_async(url: &str) -> Result<(String, Duration), ()> {
+    let result = reqwest::get(url).await.map_err(|_| ())?;
+    let max_age = cache_max_age(result.headers());
+    let text = result.text().await.map_err(|_| ())?;
+    // reject documents the providers could not use
+    serde_json::from_str::<JsonWebKeySet>(&text).map_err(|_| ())?;
+    Ok((text, max_age))
 }
 
 #[cfg(feature = "blocking")]
-impl KeyProvider for GoogleKeyProvider {
+impl KeyProvider for JwksKeyProvider {
     fn get_key(&mut self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
-        if let Some(ref cached_keys) = self.cached {
-            if self.expiration_time > Instant::now() {
-                return Ok(cached_keys.get_key(key_id));
-            }
+        let cache = self.snapshot();
+        let now = Instant::now();
+        let (keys, downloaded) = match (cache.fresh_keys(now), &cache.keys) {
+            (Some(keys), _) => (keys.clone(), false),
+            (None, Some(keys)) if now < cache.next_attempt => (keys.clone(), false),
+            _ => match self.download_keys() {
+                Ok(keys) => (keys, true),
+                Err(_) => (self.download_failed()?, false),
+            },
+        };
+        if let Some(key) = keys.get_key(key_id) {
+            return Ok(Some(key));
+        }
+        if downloaded || !self.start_forced_refresh() {
+            return Ok(None);
         }
-        Ok(self.download_keys()?.get_key(key_id))
+        let keys = match self.download_keys() {
+            Ok(keys) => keys,
+            Err(_) => self.download_failed()?,
+        };
+        Ok(keys.get_key(key_id))
     }
 }
 
 #[cfg(feature = "async")]
 #[async_trait]
-impl AsyncKeyProvider for GoogleKeyProvider {
-    async fn get_key_async(&mut self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
-        if let Some(ref cached_keys) = self.cached {
-            if self.expiration_time > Instant::now() {
-                return Ok(cached_keys.get_key(key_id));
+impl AsyncKeyProvider for JwksKeyProvider {
+    async fn get_key_async(&self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+        let cache = self.snapshot();
+        let now = Instant::now();
+        let keys = match cache.fresh_keys(now) {
+            Some(keys) => {
+                if now >= cache.refresh_time && now >= cache.next_attempt {
+                    self.spawn_background_refresh();
+                }
+                keys.clone()
             }
+            // the set was (re)loaded just now, so a missing key is not a stale cache
+            None => return Ok(self.refresh_async(false).await?.get_key(key_id)),
+        };
+        match keys.get_key(key_id) {
+            Some(key) => Ok(Some(key)),
+            None => self.refresh_for_unknown_key_async(key_id).await,
         }
-        Ok(self.download_keys_async().await?.get_key(key_id))
     }
 }
 
@@ -101,10 +325,9 @@ pub fn test_google_provider() {
 #[cfg(all(test, feature = "async"))]
 mod async_test {
     use super::{AsyncKeyProvider, GoogleKeyProvider};
-    use tokio;
     #[tokio::test]
     async fn test_google_provider_async() {
-        let mut provider = GoogleKeyProvider::default();
+        let provider = GoogleKeyProvider::default();
         assert!(provider.get_key_async("test").await.is_ok());
         assert!(provider.get_key_async("test").await.is_ok());
     }
@@ -3,18 +3,53 @@ mod test;
 
 mod algorithm;
 mod async_client;
+mod binding;
 mod client;
+mod clock;
+mod discovery;
 mod error;
+mod firebase;
 mod header;
 mod jwk;
 mod key_provider;
+mod offline_key_provider;
+mod revocation;
+mod signer;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod token;
+#[cfg(feature = "async")]
+mod tokeninfo;
 mod unverified_token;
+mod validation;
 
-pub use crate::async_client::AsyncClient;
+pub use crate::algorithm::Algorithm;
+pub use crate::async_client::{AsyncClient, GenericClient as GenericAsyncClient};
+pub use crate::binding::TokenBinding;
 pub use crate::client::Client;
+pub use crate::clock::{Clock, SystemClock};
+pub use crate::discovery::ProviderMetadata;
+pub use crate::firebase::{firebase_issuer, FirebasePayload, FIREBASE_JWKS_URL};
+pub use crate::jwk::{JsonWebKey, JsonWebKeySet};
+#[cfg(feature = "blocking")]
+pub use crate::key_provider::KeyProvider;
+#[cfg(feature = "async")]
+pub use crate::key_provider::{download_key_set_async, AsyncKeyProvider};
+pub use crate::key_provider::{
+    ForcedRefreshThrottle, GoogleKeyProvider, JwksKeyProvider, GOOGLE_CERT_URL,
+};
+pub use crate::offline_key_provider::OfflineKeyProvider;
+#[cfg(feature = "async")]
+pub use crate::revocation::AsyncRevocationList;
+pub use crate::revocation::{jwt_revocation_id, revocation_id};
+pub use crate::signer::TokenSigner;
 pub use crate::token::{IdPayload, RequiredClaims, Token};
-pub use error::Error;
+#[cfg(feature = "async")]
+pub use crate::tokeninfo::{
+    AccessTokenInfo, TokenInfoClient, TokenInfoClientBuilder, GOOGLE_TOKENINFO_URL,
+};
+pub use crate::unverified_token::{decode_unverified, DecodedToken};
+pub use error::{Error, Segment};
 
 fn base64_decode(input: &str) -> Result<Vec<u8>, base64::DecodeError> {
     base64::decode_config(&input, base64::URL_SAFE)
@@ -0,0 +1,154 @@
+use crate::jwk::{JsonWebKey, JsonWebKeySet};
+#[cfg(feature = "async")]
+use crate::key_provider::AsyncKeyProvider;
+#[cfg(feature = "blocking")]
+use crate::key_provider::KeyProvider;
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the files behind a provider are checked for changes.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serves a JSON Web Key Set from inline JSON, a file, or a directory of `.json` key
+/// set files whose keys are merged, for environments without access to the provider's
+/// JWKS endpoint.
+///
+/// File backed providers reload their keys when a file's modification time changes,
+/// checking at most once per second. A file that fails to parse, e.g. while it is
+/// being rewritten, leaves the previous keys in place. Clones share the same keys.
+#[derive(Clone)]
+pub struct OfflineKeyProvider {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    path: Option<PathBuf>,
+    state: RwLock<State>,
+}
+
+struct State {
+    keys: Arc<JsonWebKeySet>,
+    modified: Vec<(PathBuf, SystemTime)>,
+    next_check: Instant,
+}
+
+impl OfflineKeyProvider {
+    pub fn from_json(jwks: &str) -> Result<Self, serde_json::Error> {
+        let keys: JsonWebKeySet = serde_json::from_str(jwks)?;
+        Ok(Self::with_state(None, keys, Vec::new()))
+    }
+
+    /// Loads a key set file, or every `.json` file of a directory.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let modified = modification_times(&path)?;
+        let keys = load_key_sets(&modified)?;
+        Ok(Self::with_state(Some(path), keys, modified))
+    }
+
+    fn with_state(
+        path: Option<PathBuf>,
+        keys: JsonWebKeySet,
+        modified: Vec<(PathBuf, SystemTime)>,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                path,
+                state: RwLock::new(State {
+                    keys: Arc::new(keys),
+                    modified,
+                    next_check: Instant::now() + RELOAD_CHECK_INTERVAL,
+                }),
+            }),
+        }
+    }
+
+    pub fn get_path(&self) -> Option<&Path> {
+        self.shared.path.as_deref()
+    }
+
+    fn current_keys(&self) -> Arc<JsonWebKeySet> {
+        if let Some(ref path) = self.shared.path {
+            self.reload_if_changed(path);
+        }
+        self.shared.state.read().unwrap().keys.clone()
+    }
+
+    fn reload_if_changed(&self, path: &Path) {
+        let now = Instant::now();
+        {
+            let mut state = self.shared.state.write().unwrap();
+            if now < state.next_check {
+                return;
+            }
+            state.next_check = now + RELOAD_CHECK_INTERVAL;
+        }
+        let modified = match modification_times(path) {
+            Ok(modified) => modified,
+            Err(_) => return,
+        };
+        if modified == self.shared.state.read().unwrap().modified {
+            return;
+        }
+        if let Ok(keys) = load_key_sets(&modified) {
+            let mut state = self.shared.state.write().unwrap();
+            state.keys = Arc::new(keys);
+            state.modified = modified;
+        }
+    }
+}
+
+/// The key set files behind `path` with their modification times, in a stable order.
+fn modification_times(path: &Path) -> io::Result<Vec<(PathBuf, SystemTime)>> {
+    let mut files = if path.is_dir() {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let file = entry?.path();
+            if file.is_file() && file.extension().is_some_and(|ext| ext == "json") {
+                files.push(file);
+            }
+        }
+        files
+    } else {
+        vec![path.to_owned()]
+    };
+    files.sort();
+    files
+        .into_iter()
+        .map(|file| {
+            let modified = fs::metadata(&file)?.modified()?;
+            Ok((file, modified))
+        })
+        .collect()
+}
+
+fn load_key_sets(files: &[(PathBuf, SystemTime)]) -> io::Result<JsonWebKeySet> {
+    let mut keys = JsonWebKeySet::empty();
+    for (file, _) in files {
+        let set: JsonWebKeySet = serde_json::from_slice(&fs::read(file)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        keys.extend(set);
+    }
+    Ok(keys)
+}
+
+#[cfg(feature = "blocking")]
+impl KeyProvider for OfflineKeyProvider {
+    fn get_key(&mut self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+        Ok(self.current_keys().get_key(key_id))
+    }
+}
+
+/// Reads the key files synchronously; they are small and only read when they changed.
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncKeyProvider for OfflineKeyProvider {
+    async fn get_key_async(&self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+        Ok(self.current_keys().get_key(key_id))
+    }
+}
@@ -0,0 +1,51 @@
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+use openssl::hash::{hash, MessageDigest};
+use serde_derive::Deserialize;
+
+/// A store of tokens that were revoked before they expired.
+///
+/// Entries are keyed by [`revocation_id`] and only need to be kept until the revoked
+/// token's `exp`, after which the token is rejected anyway.
+#[cfg(feature = "async")]
+#[async_trait]
+pub trait AsyncRevocationList: Send + Sync {
+    async fn is_revoked_async(&self, revocation_id: &str) -> Result<bool, ()>;
+}
+
+#[derive(Deserialize)]
+struct JwtId {
+    iss: Option<String>,
+    jti: Option<String>,
+}
+
+/// The key a token is revoked under: its `iss` and `jti` claims, separated by a space,
+/// when it is a JWT carrying a `jti`, so that the IDs of different issuers cannot
+/// collide. Otherwise the SHA-256 hash of the whole token, so opaque access tokens can
+/// be revoked too.
+///
+/// The token is not verified; verify it before trusting the `jti`.
+pub fn revocation_id(token_string: &str) -> String {
+    jwt_id(token_string).unwrap_or_else(|| {
+        let hashed = hash(MessageDigest::sha256(), token_string.as_bytes())
+            .expect("SHA-256 is always available");
+        format!(
+            "sha256:{}",
+            base64::encode_config(&hashed, base64::URL_SAFE_NO_PAD)
+        )
+    })
+}
+
+fn jwt_id(token_string: &str) -> Option<String> {
+    let payload = token_string.split('.').nth(1)?;
+    let payload = crate::base64_decode(payload).ok()?;
+    let claims: JwtId = serde_json::from_slice(&payload).ok()?;
+    let jti = claims.jti.filter(|jti| !jti.is_empty())?;
+    Some(jwt_revocation_id(&claims.iss.unwrap_or_default(), &jti))
+}
+
+/// The key a JWT with the given `iss` and `jti` is revoked under, for revoking a token
+/// that is not at hand. Matches [`revocation_id`] of the token.
+pub fn jwt_revocation_id(issuer: &str, jti: &str) -> String {
+    format!("{} {}", issuer, jti)
+}
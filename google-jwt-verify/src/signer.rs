@@ -0,0 +1,135 @@
+use crate::algorithm::Algorithm;
+use crate::error::Error;
+use crate::offline_key_provider::OfflineKeyProvider;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::sign::Signer;
+use serde::Serialize;
+use serde_json::json;
+
+/// Signs tokens with a private key, for applications that issue their own tokens and
+/// verify them with this crate's clients.
+///
+/// RSA keys sign RS256 tokens, P-256 and P-384 keys ES256 and ES384 tokens. The public
+/// key is published as a JSON Web Key Set, e.g. through [`TokenSigner::key_provider`].
+pub struct TokenSigner {
+    key: PKey<Private>,
+    algorithm: Algorithm,
+    key_id: String,
+    jwks: String,
+}
+
+impl TokenSigner {
+    /// Loads a PEM encoded RSA or EC private key.
+    pub fn from_pem(pem: &[u8], key_id: &str) -> Result<Self, Error> {
+        Self::from_key(PKey::private_key_from_pem(pem)?, key_id)
+    }
+
+    /// Generates a 2048 bit RSA key, for tokens that only need to outlive the process.
+    pub fn generate(key_id: &str) -> Result<Self, Error> {
+        Self::from_key(PKey::from_rsa(Rsa::generate(2048)?)?, key_id)
+    }
+
+    fn from_key(key: PKey<Private>, key_id: &str) -> Result<Self, Error> {
+        let (algorithm, parameters) = match key.id() {
+            Id::RSA => {
+                let rsa = key.rsa()?;
+                let parameters = json!({
+                    "kty": "RSA",
+                    "n": base64_encode(&rsa.n().to_vec()),
+                    "e": base64_encode(&rsa.e().to_vec()),
+                });
+                (Algorithm::RS256, parameters)
+            }
+            Id::EC => {
+                let ec = key.ec_key()?;
+                let (algorithm, curve, size) = match ec.group().curve_name() {
+                    Some(Nid::X9_62_PRIME256V1) => (Algorithm::ES256, "P-256", 32),
+                    Some(Nid::SECP384R1) => (Algorithm::ES384, "P-384", 48),
+                    _ => return Err(Error::InvalidKey),
+                };
+                let mut x = BigNum::new()?;
+                let mut y = BigNum::new()?;
+                let mut context = BigNumContext::new()?;
+                ec.public_key()
+                    .affine_coordinates_gfp(ec.group(), &mut x, &mut y, &mut context)?;
+                let parameters = json!({
+                    "kty": "EC",
+                    "crv": curve,
+                    "x": base64_encode(&x.to_vec_padded(size)?),
+                    "y": base64_encode(&y.to_vec_padded(size)?),
+                });
+                (algorithm, parameters)
+            }
+            _ => return Err(Error::InvalidKey),
+        };
+        let mut jwk = parameters;
+        jwk["alg"] = json!(algorithm);
+        jwk["use"] = json!("sig");
+        jwk["kid"] = json!(key_id);
+        Ok(Self {
+            key,
+            algorithm,
+            key_id: key_id.to_owned(),
+            jwks: json!({ "keys": [jwk] }).to_string(),
+        })
+    }
+
+    pub fn get_algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+    pub fn get_key_id(&self) -> &str {
+        &self.key_id
+    }
+    /// The public key as a JSON Web Key Set document.
+    pub fn get_jwks(&self) -> &str {
+        &self.jwks
+    }
+    /// Serves the public key to a client verifying this signer's tokens.
+    pub fn key_provider(&self) -> OfflineKeyProvider {
+        OfflineKeyProvider::from_json(&self.jwks).expect("generated key set is valid")
+    }
+
+    /// Signs `claims` as the payload of a token.
+    pub fn sign<C: Serialize>(&self, claims: &C) -> Result<String, Error> {
+        let header = json!({
+            "alg": self.algorithm,
+            "typ": "JWT",
+            "kid": self.key_id,
+        });
+        let claims = serde_json::to_vec(claims).map_err(Error::InvalidPayload)?;
+        let body = format!(
+            "{}.{}",
+            base64_encode(header.to_string().as_bytes()),
+            base64_encode(&claims),
+        );
+        let signature = match self.algorithm {
+            Algorithm::ES256 | Algorithm::ES384 => {
+                let (digest, size) = match self.algorithm {
+                    Algorithm::ES256 => (MessageDigest::sha256(), 32),
+                    _ => (MessageDigest::sha384(), 48),
+                };
+                // JWS signatures are the raw r and s values, not the DER structure OpenSSL emits
+                let signature =
+                    EcdsaSig::sign(&hash(digest, body.as_bytes())?, &*self.key.ec_key()?)?;
+                let mut raw = signature.r().to_vec_padded(size)?;
+                raw.extend(signature.s().to_vec_padded(size)?);
+                raw
+            }
+            _ => {
+                let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+                signer.update(body.as_bytes())?;
+                signer.sign_to_vec()?
+            }
+        };
+        Ok(format!("{}.{}", body, base64_encode(&signature)))
+    }
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    base64::encode_config(input, base64::URL_SAFE_NO_PAD)
+}
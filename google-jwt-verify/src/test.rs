@@ -1,4 +1,5 @@
 use super::*;
+use crate::clock::Clock;
 use crate::error::Error;
 use crate::jwk::JsonWebKey;
 use crate::jwk::JsonWebKeySet;
@@ -6,12 +7,16 @@ use crate::jwk::JsonWebKeySet;
 use crate::key_provider::AsyncKeyProvider;
 #[cfg(feature = "blocking")]
 use crate::key_provider::KeyProvider;
+use crate::unverified_token::UnverifiedToken;
+use crate::validation::Validation;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 #[cfg(feature = "async")]
 use async_trait::async_trait;
 
-const TOKEN: &'static str = "eyJhbGciOiJSUzI1NiIsImtpZCI6ImE3NDhlOWY3NjcxNTlmNjY3YTAyMjMzMThkZTBiMjMyOWU1NDQzNjIifQ.eyJhenAiOiIzNzc3MjExNzQwOC1xanFvOWhjYTUxM3BkY3VudW10N2drMDhpaTZ0ZThpcy5hcHBzLmdvb2dsZXVzZXJjb250ZW50LmNvbSIsImF1ZCI6IjM3NzcyMTE3NDA4LXFqcW85aGNhNTEzcGRjdW51bXQ3Z2swOGlpNnRlOGlzLmFwcHMuZ29vZ2xldXNlcmNvbnRlbnQuY29tIiwic3ViIjoiMTA3MDY3MzYxNTAzOTU0NDc0NDg4IiwiZW1haWwiOiJmdWNoc25qQGdtYWlsLmNvbSIsImVtYWlsX3ZlcmlmaWVkIjp0cnVlLCJhdF9oYXNoIjoiaTBOWk5kYWp3UklJbDJvUk9zUUptUSIsImV4cCI6MTUyNjQ5MjUzMywiaXNzIjoiYWNjb3VudHMuZ29vZ2xlLmNvbSIsImp0aSI6IjNmMjc1YjRiY2JmZDU0Y2IxNjZmMzcxNWQ1NTBkMWNmMmUxYThiZGEiLCJpYXQiOjE1MjY0ODg5MzMsIm5hbWUiOiJOYXRoYW4gRm94IiwicGljdHVyZSI6Imh0dHBzOi8vbGg1Lmdvb2dsZXVzZXJjb250ZW50LmNvbS8tbEJSLWE3Z2gwdFkvQUFBQUFBQUFBQUkvQUFBQUFBQUFFUk0vNDFHUk43cDNNVzQvczk2LWMvcGhvdG8uanBnIiwiZ2l2ZW5fbmFtZSI6Ik5hdGhhbiIsImZhbWlseV9uYW1lIjoiRm94IiwibG9jYWxlIjoiZW4ifQ.pOoIMLZgZIFP-fgQirCRRK31ap_CO7WZDeHge-U5GoAvF0VdkoSDSL-1-8d93qKb8IWzi2iS2MgaLekcX8eELM5x39Th1sBwjQGjYr5AXmqE53WDQiqvKzrz-BZ3ay0uSAMllxWfFi62BkSP3m1HJNWyUWrUf6GyI-Vy024dtrX9Qq_BOznJWbQVhHf5aA7x5AAoLHZ_PmzxbUlDQ7Go6FD7sgkoksZI4Cp77HZJMXXGVOrvvXJkpctTcuBZ2P-2filLmb29JIm0e4McOjeHQTV7XNGdzTZoyeSZcU5xTVFQK89e-SIPHKyaL7TAr_faBbTGzVryYfa2VFyKi7Z9gA";
-const JWKS: &'static str = r#"{
+const TOKEN: &str = "eyJhbGciOiJSUzI1NiIsImtpZCI6ImE3NDhlOWY3NjcxNTlmNjY3YTAyMjMzMThkZTBiMjMyOWU1NDQzNjIifQ.eyJhenAiOiIzNzc3MjExNzQwOC1xanFvOWhjYTUxM3BkY3VudW10N2drMDhpaTZ0ZThpcy5hcHBzLmdvb2dsZXVzZXJjb250ZW50LmNvbSIsImF1ZCI6IjM3NzcyMTE3NDA4LXFqcW85aGNhNTEzcGRjdW51bXQ3Z2swOGlpNnRlOGlzLmFwcHMuZ29vZ2xldXNlcmNvbnRlbnQuY29tIiwic3ViIjoiMTA3MDY3MzYxNTAzOTU0NDc0NDg4IiwiZW1haWwiOiJmdWNoc25qQGdtYWlsLmNvbSIsImVtYWlsX3ZlcmlmaWVkIjp0cnVlLCJhdF9oYXNoIjoiaTBOWk5kYWp3UklJbDJvUk9zUUptUSIsImV4cCI6MTUyNjQ5MjUzMywiaXNzIjoiYWNjb3VudHMuZ29vZ2xlLmNvbSIsImp0aSI6IjNmMjc1YjRiY2JmZDU0Y2IxNjZmMzcxNWQ1NTBkMWNmMmUxYThiZGEiLCJpYXQiOjE1MjY0ODg5MzMsIm5hbWUiOiJOYXRoYW4gRm94IiwicGljdHVyZSI6Imh0dHBzOi8vbGg1Lmdvb2dsZXVzZXJjb250ZW50LmNvbS8tbEJSLWE3Z2gwdFkvQUFBQUFBQUFBQUkvQUFBQUFBQUFFUk0vNDFHUk43cDNNVzQvczk2LWMvcGhvdG8uanBnIiwiZ2l2ZW5fbmFtZSI6Ik5hdGhhbiIsImZhbWlseV9uYW1lIjoiRm94IiwibG9jYWxlIjoiZW4ifQ.pOoIMLZgZIFP-fgQirCRRK31ap_CO7WZDeHge-U5GoAvF0VdkoSDSL-1-8d93qKb8IWzi2iS2MgaLekcX8eELM5x39Th1sBwjQGjYr5AXmqE53WDQiqvKzrz-BZ3ay0uSAMllxWfFi62BkSP3m1HJNWyUWrUf6GyI-Vy024dtrX9Qq_BOznJWbQVhHf5aA7x5AAoLHZ_PmzxbUlDQ7Go6FD7sgkoksZI4Cp77HZJMXXGVOrvvXJkpctTcuBZ2P-2filLmb29JIm0e4McOjeHQTV7XNGdzTZoyeSZcU5xTVFQK89e-SIPHKyaL7TAr_faBbTGzVryYfa2VFyKi7Z9gA";
+const JWKS: &str = r#"{
  "keys": [
   {
    "kty": "RSA",
@@ -31,9 +36,9 @@ const JWKS: &'static str = r#"{
   }
  ]
 }"#;
-const AUDIENCE: &'static str =
-    "37772117408-qjqo9hca513pdcunumt7gk08ii6te8is.apps.googleusercontent.com";
+const AUDIENCE: &str = "37772117408-qjqo9hca513pdcunumt7gk08ii6te8is.apps.googleusercontent.com";
 
+#[derive(Clone)]
 struct TestKeyProvider;
 
 #[cfg(feature = "blocking")]
@@ -47,7 +52,7 @@ impl KeyProvider for TestKeyProvider {
 #[cfg(feature = "async")]
 #[async_trait]
 impl AsyncKeyProvider for TestKeyProvider {
-    async fn get_key_async(&mut self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+    async fn get_key_async(&self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
         let set: JsonWebKeySet = serde_json::from_str(JWKS).unwrap();
         Ok(set.get_key(key_id))
     }
@@ -81,7 +86,10 @@ pub fn test_client_invalid_client_id() {
         .custom_key_provider(TestKeyProvider)
         .build();
     let result = client.verify_token(TOKEN).map(|_| ());
-    assert_eq!(result, Err(Error::InvalidToken))
+    assert_eq!(
+        result,
+        Err(Error::InvalidAudience(vec![AUDIENCE.to_owned()]))
+    )
 }
 
 #[cfg(feature = "blocking")]
@@ -97,6 +105,14 @@ pub fn test_id_token() {
     assert_eq!(id_token.get_claims().get_audience(), AUDIENCE);
     assert_eq!(id_token.get_payload().get_domain(), None);
     assert_eq!(id_token.get_payload().get_email(), "fuchsnj@gmail.com");
+    assert_eq!(
+        id_token.get_payload().get_name(),
+        Some("Nathan Fox".to_owned())
+    );
+    assert_eq!(
+        id_token.get_payload().get_jwt_id(),
+        Some("3f275b4bcbfd54cb166f3715d550d1cf2e1a8bda".to_owned())
+    );
 }
 
 #[cfg(feature = "async")]
@@ -132,7 +148,10 @@ async fn test_client_invalid_client_id_async() {
         .custom_key_provider(TestKeyProvider)
         .build();
     let result = client.verify_token_async(TOKEN).await.map(|_| ());
-    assert_eq!(result, Err(Error::InvalidToken))
+    assert_eq!(
+        result,
+        Err(Error::InvalidAudience(vec![AUDIENCE.to_owned()]))
+    )
 }
 
 #[cfg(feature = "async")]
@@ -150,3 +169,1252 @@ async fn test_id_token_async() {
     assert_eq!(id_token.get_payload().get_domain(), None);
     assert_eq!(id_token.get_payload().get_email(), "fuchsnj@gmail.com");
 }
+
+#[test]
+pub fn test_id_payload_optional_and_extra_claims() {
+    let payload: IdPayload = serde_json::from_value(serde_json::json!({
+        "iss": "accounts.google.com",
+        "sub": "1234",
+        "email": "someone@example.com",
+        "email_verified": true,
+        "jti": "token-id",
+        "nonce": "n-0S6_WzA2Mj",
+        "auth_time": 1526488900,
+        "amr": ["pwd", "mfa"],
+        "firebase": { "sign_in_provider": "google.com" },
+    }))
+    .unwrap();
+    assert_eq!(payload.get_name(), None);
+    assert_eq!(payload.get_picture_url(), None);
+    assert_eq!(payload.get_locale(), None);
+    assert_eq!(payload.get_jwt_id(), Some("token-id".to_owned()));
+    assert_eq!(payload.get_nonce(), Some("n-0S6_WzA2Mj".to_owned()));
+    assert_eq!(payload.get_auth_time(), Some(1526488900));
+    assert_eq!(
+        payload.get_authentication_methods(),
+        Some(vec!["pwd".to_owned(), "mfa".to_owned()])
+    );
+    assert_eq!(
+        payload.get_extra_claim("firebase"),
+        Some(&serde_json::json!({ "sign_in_provider": "google.com" }))
+    );
+    // registered claims are exposed through RequiredClaims instead
+    assert_eq!(payload.get_extra_claims().len(), 1);
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    base64::encode_config(input, base64::URL_SAFE_NO_PAD)
+}
+
+fn rsa_key_and_jwk(alg: &str) -> (openssl::pkey::PKey<openssl::pkey::Private>, JsonWebKey) {
+    let rsa = openssl::rsa::Rsa::generate(2048).unwrap();
+    let jwk = serde_json::from_value(serde_json::json!({
+        "kty": "RSA",
+        "alg": alg,
+        "kid": "rsa-test",
+        "n": base64_encode(&rsa.n().to_vec()),
+        "e": base64_encode(&rsa.e().to_vec()),
+    }))
+    .unwrap();
+    (openssl::pkey::PKey::from_rsa(rsa).unwrap(), jwk)
+}
+
+fn ec_key_and_jwk(
+    alg: &str,
+    crv: &str,
+    curve: openssl::nid::Nid,
+) -> (openssl::ec::EcKey<openssl::pkey::Private>, JsonWebKey) {
+    let group = openssl::ec::EcGroup::from_curve_name(curve).unwrap();
+    let key = openssl::ec::EcKey::generate(&group).unwrap();
+    let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+    let mut x = openssl::bn::BigNum::new().unwrap();
+    let mut y = openssl::bn::BigNum::new().unwrap();
+    key.public_key()
+        .affine_coordinates_gfp(&group, &mut x, &mut y, &mut ctx)
+        .unwrap();
+    let jwk = serde_json::from_value(serde_json::json!({
+        "kty": "EC",
+        "alg": alg,
+        "kid": "ec-test",
+        "crv": crv,
+        "x": base64_encode(&x.to_vec()),
+        "y": base64_encode(&y.to_vec()),
+    }))
+    .unwrap();
+    (key, jwk)
+}
+
+fn sign_ec(
+    key: &openssl::ec::EcKey<openssl::pkey::Private>,
+    digest: openssl::hash::MessageDigest,
+    width: i32,
+    body: &[u8],
+) -> Vec<u8> {
+    let hashed = openssl::hash::hash(digest, body).unwrap();
+    let signature = openssl::ecdsa::EcdsaSig::sign(&hashed, key).unwrap();
+    let mut raw = signature.r().to_vec_padded(width).unwrap();
+    raw.extend(signature.s().to_vec_padded(width).unwrap());
+    raw
+}
+
+#[test]
+pub fn test_verify_rs384_and_rs512() {
+    let body = b"header.payload";
+    for (alg, digest) in [
+        ("RS384", openssl::hash::MessageDigest::sha384()),
+        ("RS512", openssl::hash::MessageDigest::sha512()),
+    ]
+    .iter()
+    {
+        let (key, jwk) = rsa_key_and_jwk(alg);
+        let algorithm = alg.parse().unwrap();
+        let mut signer = openssl::sign::Signer::new(*digest, &key).unwrap();
+        signer.update(body).unwrap();
+        let signature = signer.sign_to_vec().unwrap();
+        assert_eq!(jwk.verify(algorithm, body, &signature), Ok(()));
+        assert_eq!(
+            jwk.verify(algorithm, b"tampered", &signature),
+            Err(Error::InvalidSignature)
+        );
+    }
+}
+
+#[test]
+pub fn test_verify_es256_and_es384() {
+    let body = b"header.payload";
+    for (alg, crv, curve, digest, width) in [
+        (
+            "ES256",
+            "P-256",
+            openssl::nid::Nid::X9_62_PRIME256V1,
+            openssl::hash::MessageDigest::sha256(),
+            32,
+        ),
+        (
+            "ES384",
+            "P-384",
+            openssl::nid::Nid::SECP384R1,
+            openssl::hash::MessageDigest::sha384(),
+            48,
+        ),
+    ]
+    .iter()
+    {
+        let (key, jwk) = ec_key_and_jwk(alg, crv, *curve);
+        let algorithm = alg.parse().unwrap();
+        let signature = sign_ec(&key, *digest, *width, body);
+        assert_eq!(jwk.verify(algorithm, body, &signature), Ok(()));
+        assert_eq!(
+            jwk.verify(algorithm, b"tampered", &signature),
+            Err(Error::InvalidSignature)
+        );
+    }
+}
+
+#[test]
+pub fn test_verify_ec_curve_mismatch() {
+    let (key, jwk) = ec_key_and_jwk("ES384", "P-256", openssl::nid::Nid::X9_62_PRIME256V1);
+    let signature = sign_ec(&key, openssl::hash::MessageDigest::sha384(), 32, b"body");
+    assert_eq!(
+        jwk.verify(Algorithm::ES384, b"body", &signature),
+        Err(Error::AlgorithmMismatch(Algorithm::ES384))
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_client_multiple_audiences() {
+    let client = Client::builder_with_audiences(&["web client id", AUDIENCE])
+        .custom_key_provider(TestKeyProvider)
+        .unsafe_ignore_expiration()
+        .build();
+    assert!(client.verify_id_token(TOKEN).is_ok());
+
+    let client = Client::builder("web client id")
+        .audience("ios client id")
+        .audience(AUDIENCE)
+        .custom_key_provider(TestKeyProvider)
+        .unsafe_ignore_expiration()
+        .check_authorized_party()
+        .build();
+    assert!(client.verify_id_token(TOKEN).is_ok());
+}
+
+fn claims(audience: &str, authorized_party: &str) -> RequiredClaims {
+    serde_json::from_value(serde_json::json!({
+        "iss": "accounts.google.com",
+        "sub": "1234",
+        "aud": audience,
+        "azp": authorized_party,
+        "iat": 1,
+        "exp": 2,
+    }))
+    .unwrap()
+}
+
+#[test]
+pub fn test_validation_authorized_party() {
+    let mut validation = Validation::new(&["web", "android"]);
+    validation.set_check_expiration(false);
+    assert_eq!(
+        validation.validate_claims(&claims("web", "android")),
+        Ok(())
+    );
+    assert_eq!(validation.validate_claims(&claims("web", "other")), Ok(()));
+    assert_eq!(
+        validation.validate_claims(&claims("other", "android")),
+        Err(Error::InvalidAudience(vec!["other".to_owned()]))
+    );
+
+    validation.set_check_authorized_party(true);
+    assert_eq!(
+        validation.validate_claims(&claims("web", "android")),
+        Ok(())
+    );
+    assert_eq!(
+        validation.validate_claims(&claims("web", "other")),
+        Err(Error::InvalidAuthorizedParty(Some("other".to_owned())))
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_client_hosted_domain() {
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(TestKeyProvider)
+        .unsafe_ignore_expiration()
+        .hosted_domain("example.com")
+        .build();
+    assert_eq!(
+        client.verify_id_token(TOKEN).map(|_| ()),
+        Err(Error::InvalidHostedDomain)
+    );
+}
+
+#[test]
+pub fn test_validation_hosted_domain() {
+    let mut validation = Validation::new(&[AUDIENCE]);
+    assert_eq!(validation.validate_hosted_domain(b"{}"), Ok(()));
+
+    validation.add_hosted_domain("example.com");
+    validation.add_hosted_domain("example.org");
+    assert_eq!(
+        validation.validate_hosted_domain(br#"{"hd":"example.org"}"#),
+        Ok(())
+    );
+    assert_eq!(
+        validation.validate_hosted_domain(br#"{"hd":"gmail.com"}"#),
+        Err(Error::InvalidHostedDomain)
+    );
+    assert_eq!(
+        validation.validate_hosted_domain(b"{}"),
+        Err(Error::InvalidHostedDomain)
+    );
+}
+
+struct FixedClock(u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(self.0)
+    }
+}
+
+// `exp` of TOKEN
+const TOKEN_EXPIRES_AT: u64 = 1526492533;
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_client_clock_and_leeway() {
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(TestKeyProvider)
+        .clock(FixedClock(TOKEN_EXPIRES_AT))
+        .build();
+    assert!(client.verify_id_token(TOKEN).is_ok());
+
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(TestKeyProvider)
+        .clock(FixedClock(TOKEN_EXPIRES_AT + 30))
+        .build();
+    assert_eq!(
+        client.verify_id_token(TOKEN).map(|_| ()),
+        Err(Error::Expired)
+    );
+
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(TestKeyProvider)
+        .clock(FixedClock(TOKEN_EXPIRES_AT + 30))
+        .leeway(std::time::Duration::from_secs(60))
+        .build();
+    assert!(client.verify_id_token(TOKEN).is_ok());
+}
+
+fn timed_claims(issued_at: u64, not_before: Option<u64>, expires_at: u64) -> RequiredClaims {
+    serde_json::from_value(serde_json::json!({
+        "iss": "accounts.google.com",
+        "sub": "1234",
+        "aud": AUDIENCE,
+        "azp": AUDIENCE,
+        "iat": issued_at,
+        "nbf": not_before,
+        "exp": expires_at,
+    }))
+    .unwrap()
+}
+
+#[test]
+pub fn test_validation_not_before() {
+    let claims = timed_claims(1000, Some(1100), 5000);
+    let mut validation = Validation::new(&[AUDIENCE]);
+    validation.set_clock(FixedClock(1050));
+    assert_eq!(validation.validate_claims(&claims), Ok(()));
+
+    validation.set_check_not_before(true);
+    assert_eq!(validation.validate_claims(&claims), Err(Error::NotYetValid));
+
+    validation.set_leeway(std::time::Duration::from_secs(60));
+    assert_eq!(validation.validate_claims(&claims), Ok(()));
+}
+
+#[test]
+pub fn test_validation_max_age() {
+    let claims = timed_claims(1000, None, 5000);
+    let mut validation = Validation::new(&[AUDIENCE]);
+    validation.set_max_age(std::time::Duration::from_secs(600));
+    validation.set_clock(FixedClock(1500));
+    assert_eq!(validation.validate_claims(&claims), Ok(()));
+
+    validation.set_clock(FixedClock(1700));
+    assert_eq!(validation.validate_claims(&claims), Err(Error::Expired));
+
+    validation.set_clock(FixedClock(900));
+    assert_eq!(validation.validate_claims(&claims), Err(Error::NotYetValid));
+}
+
+/// A canned response of the stand-in provider.
+struct Canned {
+    status: &'static str,
+    cache_control: &'static str,
+    body: String,
+}
+
+impl Canned {
+    fn json(body: String) -> Canned {
+        Canned {
+            status: "200 OK",
+            cache_control: "public, max-age=3600",
+            body,
+        }
+    }
+    fn not_found() -> Canned {
+        Canned {
+            status: "404 Not Found",
+            cache_control: "no-cache",
+            body: String::new(),
+        }
+    }
+}
+
+/// Serves responses on a local port, standing in for a provider's discovery and
+/// JWKS endpoints. `handler` receives the server's base URL, the request path and
+/// the number of earlier requests. Returns the base URL and a request counter.
+fn serve<F>(handler: F) -> (String, Arc<AtomicUsize>)
+where
+    F: Fn(&str, &str, usize) -> Canned + Send + 'static,
+{
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server_base_url = base_url.clone();
+    let server_hits = hits.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut buffer = [0; 4096];
+            let read = stream.read(&mut buffer).unwrap_or(0);
+            let request = String::from_utf8_lossy(&buffer[..read]);
+            let path = request.split_whitespace().nth(1).unwrap_or("");
+            let hit = server_hits.fetch_add(1, Ordering::SeqCst);
+            let canned = handler(&server_base_url, path, hit);
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nCache-Control: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                canned.status,
+                canned.cache_control,
+                canned.body.len(),
+                canned.body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (base_url, hits)
+}
+
+fn serve_provider() -> String {
+    let (base_url, _) = serve(|base_url, path, _| match path {
+        "/.well-known/openid-configuration" => Canned::json(
+            serde_json::json!({ "issuer": base_url, "jwks_uri": format!("{}/jwks", base_url) })
+                .to_string(),
+        ),
+        "/jwks" => Canned::json(JWKS.to_owned()),
+        _ => Canned::not_found(),
+    });
+    base_url
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_jwks_provider_local_server() {
+    let base_url = serve_provider();
+    let mut provider = JwksKeyProvider::new(&format!("{}/jwks", base_url));
+    let key = provider
+        .get_key("a748e9f767159f667a0223318de0b2329e544362")
+        .unwrap()
+        .expect("key should be served");
+    assert_eq!(key.get_id(), "a748e9f767159f667a0223318de0b2329e544362");
+    assert!(provider.get_key("unknown").unwrap().is_none());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_discovered_provider() {
+    let base_url = serve_provider();
+    let metadata = ProviderMetadata::discover(&base_url).expect("discovery should succeed");
+    assert_eq!(
+        metadata,
+        ProviderMetadata::new(&base_url, &format!("{}/jwks", base_url))
+    );
+    assert_eq!(
+        ProviderMetadata::discover(&format!("{}/other", base_url)),
+        Err(Error::RetrieveKeyFailure)
+    );
+
+    // the discovered issuer is the local server, which TOKEN was not issued by
+    let client = Client::builder(AUDIENCE)
+        .openid_provider(&metadata)
+        .unsafe_ignore_expiration()
+        .build();
+    assert_eq!(
+        client.verify_id_token(TOKEN).map(|_| ()),
+        Err(Error::InvalidIssuer("accounts.google.com".to_owned()))
+    );
+
+    let client = Client::builder(AUDIENCE)
+        .openid_provider(&metadata)
+        .issuers(&["accounts.google.com"])
+        .unsafe_ignore_expiration()
+        .build();
+    assert!(client.verify_id_token(TOKEN).is_ok());
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_discovered_provider_async() {
+    let base_url = serve_provider();
+    let metadata = ProviderMetadata::discover_async(&base_url)
+        .await
+        .expect("discovery should succeed");
+    let client = AsyncClient::builder(AUDIENCE)
+        .openid_provider(&metadata)
+        .issuers(&["https://accounts.google.com", "accounts.google.com"])
+        .unsafe_ignore_expiration()
+        .build();
+    assert!(client.verify_id_token_async(TOKEN).await.is_ok());
+}
+
+#[test]
+pub fn test_jwks_skips_unsupported_keys() {
+    let set: JsonWebKeySet = serde_json::from_value(serde_json::json!({
+        "keys": [
+            { "kty": "oct", "kid": "symmetric", "k": "c2VjcmV0" },
+            { "kty": "RSA", "alg": "RSA-OAEP", "use": "enc", "kid": "encryption", "n": "AQAB", "e": "AQAB" },
+            { "kty": "RSA", "use": "sig", "kid": "no-alg", "n": "AQAB", "e": "AQAB" },
+            { "kty": "EC", "kid": "ec", "crv": "P-384", "x": "AQAB", "y": "AQAB" }
+        ]
+    }))
+    .unwrap();
+    assert!(set.get_key("symmetric").is_none());
+    assert!(set.get_key("encryption").is_none());
+    assert_eq!(
+        set.get_key("no-alg").unwrap().get_algorithm(),
+        Some(crate::algorithm::Algorithm::RS256)
+    );
+    assert_eq!(
+        set.get_key("ec").unwrap().get_algorithm(),
+        Some(crate::algorithm::Algorithm::ES384)
+    );
+}
+
+#[test]
+pub fn test_validation_issuers_and_audience_array() {
+    let claims: RequiredClaims = serde_json::from_value(serde_json::json!({
+        "iss": "https://appleid.apple.com",
+        "sub": "1234",
+        "aud": ["com.example.app", "other"],
+        "iat": 1,
+        "exp": 2,
+    }))
+    .unwrap();
+    assert_eq!(claims.get_authorized_party(), None);
+
+    let mut validation = Validation::new(&["com.example.app"]);
+    validation.set_check_expiration(false);
+    assert_eq!(
+        validation.validate_claims(&claims),
+        Err(Error::InvalidIssuer("https://appleid.apple.com".to_owned()))
+    );
+
+    validation.set_issuers(&["https://appleid.apple.com"]);
+    assert_eq!(validation.validate_claims(&claims), Ok(()));
+
+    validation.set_check_authorized_party(true);
+    assert_eq!(
+        validation.validate_claims(&claims),
+        Err(Error::InvalidAuthorizedParty(None))
+    );
+}
+
+const KEY_ID: &str = "a748e9f767159f667a0223318de0b2329e544362";
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_jwks_provider_shares_concurrent_refresh() {
+    let (base_url, hits) = serve(|_, _, _| {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        Canned::json(JWKS.to_owned())
+    });
+    let provider = JwksKeyProvider::new(&format!("{}/jwks", base_url));
+    let (a, b, c) = tokio::join!(
+        provider.get_key_async(KEY_ID),
+        provider.get_key_async(KEY_ID),
+        provider.get_key_async(KEY_ID)
+    );
+    assert!(a.unwrap().is_some() && b.unwrap().is_some() && c.unwrap().is_some());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_jwks_provider_serves_stale_keys_when_refresh_fails() {
+    let (base_url, hits) = serve(|_, _, hit| match hit {
+        0 => Canned {
+            status: "200 OK",
+            cache_control: "max-age=0",
+            body: JWKS.to_owned(),
+        },
+        _ => Canned {
+            status: "500 Internal Server Error",
+            cache_control: "no-cache",
+            body: "oops".to_owned(),
+        },
+    });
+    let provider = JwksKeyProvider::new(&format!("{}/jwks", base_url));
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_some());
+    // the set expired immediately and the endpoint now fails
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_some());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    // failed downloads are not retried on every call
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_some());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_jwks_provider_refreshes_in_background() {
+    let (base_url, hits) = serve(|_, _, _| Canned {
+        status: "200 OK",
+        cache_control: "max-age=1",
+        body: JWKS.to_owned(),
+    });
+    let provider = JwksKeyProvider::new(&format!("{}/jwks", base_url));
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_some());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // past three quarters of max-age the cached key is served and a refresh starts
+    tokio::time::delay_for(std::time::Duration::from_millis(800)).await;
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_some());
+    for _ in 0..50 {
+        if hits.load(Ordering::SeqCst) == 2 {
+            break;
+        }
+        tokio::time::delay_for(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_jwks_provider_without_keys_fails() {
+    let (base_url, _) = serve(|_, _, _| Canned::not_found());
+    let provider = JwksKeyProvider::new(&format!("{}/jwks", base_url));
+    assert!(provider.get_key_async(KEY_ID).await.is_err());
+}
+
+/// JWKS without the key that signed TOKEN, as served before a key rotation.
+fn jwks_before_rotation() -> String {
+    let mut set: serde_json::Value = serde_json::from_str(JWKS).unwrap();
+    set["keys"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|key| key["kid"] != KEY_ID);
+    set.to_string()
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_jwks_provider_refreshes_on_unknown_key_id() {
+    let (base_url, hits) = serve(|_, _, hit| match hit {
+        0 => Canned::json(jwks_before_rotation()),
+        _ => Canned::json(JWKS.to_owned()),
+    });
+    let provider = JwksKeyProvider::new(&format!("{}/jwks", base_url));
+    assert!(provider
+        .get_key_async("3f3ef9c7803cd0b8d75247ee0d31fdd5c2cf3812")
+        .await
+        .unwrap()
+        .is_some());
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_some());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_jwks_provider_rate_limits_unknown_key_ids() {
+    let (base_url, hits) = serve(|_, _, _| Canned::json(JWKS.to_owned()));
+    let provider = JwksKeyProvider::new(&format!("{}/jwks", base_url));
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_some());
+    assert!(provider.get_key_async("bogus").await.unwrap().is_none());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert!(provider
+        .get_key_async("other bogus")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_client_unknown_key_id() {
+    let (base_url, hits) = serve(|_, _, _| Canned::json(jwks_before_rotation()));
+    let client = Client::builder(AUDIENCE)
+        .jwks_url(&format!("{}/jwks", base_url))
+        .unsafe_ignore_expiration()
+        .build();
+    assert_eq!(
+        client.verify_id_token(TOKEN).map(|_| ()),
+        Err(Error::UnknownKeyId(KEY_ID.to_owned()))
+    );
+    assert_eq!(
+        client.verify_id_token(TOKEN).map(|_| ()),
+        Err(Error::UnknownKeyId(KEY_ID.to_owned()))
+    );
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+pub fn test_validate_reports_malformed_tokens() {
+    let validation = Validation::new(&[AUDIENCE]);
+    let validate = |token: &str| UnverifiedToken::<()>::validate(token, &validation).map(|_| ());
+    let mut segments = TOKEN.split('.');
+    let (header, payload, signature) = (
+        segments.next().unwrap(),
+        segments.next().unwrap(),
+        segments.next().unwrap(),
+    );
+
+    assert_eq!(validate("not a token"), Err(Error::MalformedToken));
+    assert_eq!(
+        validate(&format!("{}.{}.{}.extra", header, payload, signature)),
+        Err(Error::MalformedToken)
+    );
+    assert!(matches!(
+        validate(&format!("{}.{}.{}", header, "%%%", signature)),
+        Err(Error::MalformedSegment(Segment::Payload, _))
+    ));
+    assert!(matches!(
+        validate(&format!("{}.{}.{}", payload, payload, signature)),
+        Err(Error::InvalidHeader(_))
+    ));
+    assert!(matches!(
+        validate(&format!("{}.{}.{}", header, header, signature)),
+        Err(Error::InvalidPayload(_))
+    ));
+}
+
+#[test]
+pub fn test_decode_unverified() {
+    // decoding skips every check, including the signature
+    let mut segments = TOKEN.split('.');
+    let (header, payload) = (segments.next().unwrap(), segments.next().unwrap());
+    let decoded = decode_unverified(&format!("{}.{}.forged", header, payload)).unwrap();
+    assert_eq!(decoded.get_header()["kid"], KEY_ID);
+    assert_eq!(decoded.get_claims()["aud"], AUDIENCE);
+    assert_eq!(decoded.get_claims()["exp"], TOKEN_EXPIRES_AT);
+
+    assert_eq!(
+        decode_unverified("not a token").map(|_| ()),
+        Err(Error::MalformedToken)
+    );
+    assert!(matches!(
+        decode_unverified(&format!("{}.{}.forged", header, "%%%")),
+        Err(Error::MalformedSegment(Segment::Payload, _))
+    ));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_client_invalid_signature() {
+    let mut segments = TOKEN.split('.');
+    let (header, payload) = (segments.next().unwrap(), segments.next().unwrap());
+    let forged = format!("{}.{}.{}", header, payload, base64_encode(&[0; 256]));
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(TestKeyProvider)
+        .unsafe_ignore_expiration()
+        .build();
+    let result = client.verify_id_token(&forged).map(|_| ());
+    assert_eq!(result, Err(Error::InvalidSignature));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "token signature does not match"
+    );
+}
+
+#[cfg(all(feature = "testing", feature = "async"))]
+#[tokio::test]
+async fn test_minted_id_token_async() {
+    let issuer = crate::testing::TestIssuer::new();
+    let client = AsyncClient::builder(AUDIENCE)
+        .custom_key_provider(issuer.key_provider())
+        .build();
+    let token = issuer.google_id_token(AUDIENCE, "1234", "someone@example.com");
+    let id_token = client.verify_id_token_async(&token).await.unwrap();
+    assert_eq!(id_token.get_claims().get_subject(), "1234");
+    assert_eq!(id_token.get_payload().get_email(), "someone@example.com");
+
+    // a boxed provider, as used by applications that swap providers in tests
+    let provider: Box<dyn AsyncKeyProvider> = Box::new(issuer.key_provider());
+    let client = AsyncClient::builder(AUDIENCE)
+        .custom_key_provider(provider)
+        .build();
+    assert!(client.verify_id_token_async(&token).await.is_ok());
+}
+
+#[cfg(all(feature = "testing", feature = "blocking"))]
+#[test]
+pub fn test_minted_token_custom_claims() {
+    let issuer = crate::testing::TestIssuer::new();
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(issuer.key_provider())
+        .hosted_domain("example.com")
+        .build();
+    let mut claims = crate::testing::google_id_token_claims(AUDIENCE, "1234", "a@example.com");
+    claims["hd"] = "example.com".into();
+    let id_token = client.verify_id_token(&issuer.sign(&claims)).unwrap();
+    assert_eq!(
+        id_token.get_payload().get_domain(),
+        Some("example.com".to_owned())
+    );
+
+    // a token signed by another key with the same kid does not verify
+    let other = crate::testing::TestIssuer::new();
+    assert_eq!(
+        client.verify_id_token(&other.sign(&claims)).map(|_| ()),
+        Err(Error::InvalidSignature)
+    );
+}
+
+/// Signs `header` and `claims` with `key` as an RS256 token, whatever the header claims.
+#[cfg(feature = "blocking")]
+fn sign_rsa(
+    key: &openssl::pkey::PKey<openssl::pkey::Private>,
+    header: serde_json::Value,
+    claims: &serde_json::Value,
+) -> String {
+    let body = format!(
+        "{}.{}",
+        base64_encode(header.to_string().as_bytes()),
+        base64_encode(claims.to_string().as_bytes())
+    );
+    let mut signer =
+        openssl::sign::Signer::new(openssl::hash::MessageDigest::sha256(), key).unwrap();
+    signer.update(body.as_bytes()).unwrap();
+    format!("{}.{}", body, base64_encode(&signer.sign_to_vec().unwrap()))
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_validate_header_algorithm() {
+    let claims = serde_json::json!({
+        "iss": "accounts.google.com",
+        "aud": AUDIENCE,
+        "sub": "1234",
+        "iat": 1,
+        "exp": 2,
+    });
+    let validation = Validation::new(&[AUDIENCE]);
+    let validate = |header: serde_json::Value| {
+        let token = format!(
+            "{}.{}.",
+            base64_encode(header.to_string().as_bytes()),
+            base64_encode(claims.to_string().as_bytes())
+        );
+        UnverifiedToken::<()>::validate(&token, &validation).map(|_| ())
+    };
+    for alg in ["none", "HS256", "PS256"].iter() {
+        assert_eq!(
+            validate(serde_json::json!({ "alg": alg, "kid": KEY_ID })),
+            Err(Error::DisallowedAlgorithm(alg.to_string()))
+        );
+    }
+    assert_eq!(
+        validate(serde_json::json!({ "alg": "RS256", "typ": "at+jwt", "kid": KEY_ID })),
+        Err(Error::InvalidTokenType("at+jwt".to_owned()))
+    );
+
+    // HS256 stays rejected even when the allow-list names it
+    let mut validation = Validation::new(&[AUDIENCE]);
+    validation.set_algorithms(&[Algorithm::RS256, Algorithm::HS256]);
+    let token = format!(
+        "{}.{}.",
+        base64_encode(br#"{"alg":"HS256","kid":"k"}"#),
+        base64_encode(claims.to_string().as_bytes())
+    );
+    assert_eq!(
+        UnverifiedToken::<()>::validate(&token, &validation).map(|_| ()),
+        Err(Error::DisallowedAlgorithm("HS256".to_owned()))
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_client_algorithm_allow_list_and_key_mismatch() {
+    let (key, jwk) = rsa_key_and_jwk("RS256");
+    #[derive(Clone)]
+    struct SingleKey(JsonWebKey);
+    impl KeyProvider for SingleKey {
+        fn get_key(&mut self, _key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+            Ok(Some(self.0.clone()))
+        }
+    }
+    let claims = serde_json::json!({
+        "iss": "accounts.google.com",
+        "aud": AUDIENCE,
+        "sub": "1234",
+        "iat": 1,
+        "exp": 2,
+    });
+    let header = serde_json::json!({ "alg": "RS256", "typ": "JWT", "kid": "rsa-test" });
+    let token = sign_rsa(&key, header, &claims);
+
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(SingleKey(jwk.clone()))
+        .unsafe_ignore_expiration()
+        .build();
+    assert_eq!(
+        client
+            .verify_token_with_payload::<serde_json::Value>(&token)
+            .map(|_| ()),
+        Ok(())
+    );
+
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(SingleKey(jwk.clone()))
+        .algorithms(&[Algorithm::ES256])
+        .unsafe_ignore_expiration()
+        .build();
+    assert_eq!(
+        client
+            .verify_token_with_payload::<serde_json::Value>(&token)
+            .map(|_| ()),
+        Err(Error::DisallowedAlgorithm("RS256".to_owned()))
+    );
+
+    // an RS256 key must not verify a token that claims RS512
+    let header = serde_json::json!({ "alg": "RS512", "kid": "rsa-test" });
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(SingleKey(jwk))
+        .unsafe_ignore_expiration()
+        .build();
+    assert_eq!(
+        client
+            .verify_token_with_payload::<serde_json::Value>(&sign_rsa(&key, header, &claims))
+            .map(|_| ()),
+        Err(Error::AlgorithmMismatch(Algorithm::RS512))
+    );
+}
+
+#[test]
+pub fn test_validate_nonce_and_access_token_hash() {
+    // access token and at_hash from the OpenID Connect Core specification, appendix A.4
+    let access_token = "jHkWEdUXMU1BwAsC4vtUsZwnNvTIxEl0z9K3vx5KF0Y";
+    let claims = serde_json::json!({
+        "iss": "accounts.google.com",
+        "aud": AUDIENCE,
+        "sub": "1234",
+        "iat": 1,
+        "exp": 2,
+        "nonce": "n-0S6_WzA2Mj",
+        "at_hash": "77QmUPtjPfzWtF2AnpK9RQ",
+    });
+    let token = format!(
+        "{}.{}.",
+        base64_encode(br#"{"alg":"RS256","kid":"k"}"#),
+        base64_encode(claims.to_string().as_bytes())
+    );
+    let mut validation = Validation::new(&[AUDIENCE]);
+    validation.set_check_expiration(false);
+    let token = UnverifiedToken::<serde_json::Value>::validate(&token, &validation).unwrap();
+
+    let binding = TokenBinding::new()
+        .nonce("n-0S6_WzA2Mj")
+        .access_token(access_token);
+    assert_eq!(token.validate_binding(&binding), Ok(()));
+    assert_eq!(token.validate_binding(&TokenBinding::new()), Ok(()));
+    assert_eq!(
+        token.validate_binding(&TokenBinding::new().nonce("another nonce")),
+        Err(Error::InvalidNonce)
+    );
+    assert_eq!(
+        token.validate_binding(&TokenBinding::new().access_token("another access token")),
+        Err(Error::InvalidAccessTokenHash)
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_client_requires_nonce() {
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(TestKeyProvider)
+        .unsafe_ignore_expiration()
+        .build();
+    // the frozen token carries an at_hash but no nonce
+    assert_eq!(
+        client
+            .verify_bound_id_token(TOKEN, &TokenBinding::new().nonce("expected"))
+            .map(|_| ()),
+        Err(Error::InvalidNonce)
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_offline_provider_inline_json() {
+    let client = Client::builder(AUDIENCE)
+        .custom_key_provider(OfflineKeyProvider::from_json(JWKS).unwrap())
+        .unsafe_ignore_expiration()
+        .build();
+    assert!(client.verify_id_token(TOKEN).is_ok());
+    assert!(OfflineKeyProvider::from_json("{}").is_err());
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_offline_provider_reloads_directory() {
+    let dir = std::env::temp_dir().join(format!("jwks-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("google.json"), jwks_before_rotation()).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a key set").unwrap();
+    let provider = OfflineKeyProvider::from_path(&dir).unwrap();
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_none());
+
+    // rotate in a second file; a broken file leaves the previous keys in place
+    tokio::time::delay_for(std::time::Duration::from_millis(1100)).await;
+    std::fs::write(dir.join("rotated.json"), "{").unwrap();
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_none());
+    assert!(provider
+        .get_key_async("3f3ef9c7803cd0b8d75247ee0d31fdd5c2cf3812")
+        .await
+        .unwrap()
+        .is_some());
+
+    tokio::time::delay_for(std::time::Duration::from_millis(1100)).await;
+    std::fs::write(dir.join("rotated.json"), JWKS).unwrap();
+    assert!(provider.get_key_async(KEY_ID).await.unwrap().is_some());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_download_key_set_async() {
+    let (base_url, _) = serve(|_, path, _| match path {
+        "/jwks" => Canned::json(JWKS.to_owned()),
+        _ => Canned::json("<html></html>".to_owned()),
+    });
+    let (jwks, max_age) = download_key_set_async(&format!("{}/jwks", base_url))
+        .await
+        .unwrap();
+    assert_eq!(jwks, JWKS);
+    assert_eq!(max_age, std::time::Duration::from_secs(3600));
+    assert!(download_key_set_async(&format!("{}/other", base_url))
+        .await
+        .is_err());
+}
+
+#[cfg(all(feature = "testing", feature = "async"))]
+#[tokio::test]
+async fn test_firebase_token_async() {
+    let issuer = crate::testing::TestIssuer::new();
+    let client = AsyncClient::firebase_builder("my-project")
+        .custom_key_provider(issuer.key_provider())
+        .build();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut claims = serde_json::json!({
+        "iss": "https://securetoken.google.com/my-project",
+        "aud": "my-project",
+        "sub": "firebase-uid",
+        "user_id": "firebase-uid",
+        "auth_time": now,
+        "iat": now,
+        "exp": now + 3600,
+        "email": "someone@example.com",
+        "firebase": {
+            "sign_in_provider": "google.com",
+            "identities": { "google.com": ["1234"], "email": ["someone@example.com"] },
+        },
+    });
+    let token = client
+        .verify_firebase_token_async(&issuer.sign(&claims))
+        .await
+        .unwrap();
+    assert_eq!(token.get_payload().get_user_id(), "firebase-uid");
+    assert_eq!(token.get_payload().get_sign_in_provider(), "google.com");
+    assert_eq!(token.get_payload().get_phone_number(), None);
+    assert!(token
+        .get_payload()
+        .get_identities()
+        .contains_key("google.com"));
+
+    claims["iss"] = "https://accounts.google.com".into();
+    assert_eq!(
+        client
+            .verify_firebase_token_async(&issuer.sign(&claims))
+            .await
+            .map(|_| ()),
+        Err(Error::InvalidIssuer(
+            "https://accounts.google.com".to_owned()
+        ))
+    );
+
+    claims["iss"] = "https://securetoken.google.com/my-project".into();
+    claims["auth_time"] = (now + 600).into();
+    assert_eq!(
+        client
+            .verify_firebase_token_async(&issuer.sign(&claims))
+            .await
+            .map(|_| ()),
+        Err(Error::InvalidAuthTime)
+    );
+}
+
+#[cfg(all(feature = "testing", feature = "async"))]
+#[tokio::test]
+async fn test_client_revocation_list() {
+    struct Revoked(Vec<String>);
+
+    #[async_trait]
+    impl AsyncRevocationList for Revoked {
+        async fn is_revoked_async(&self, revocation_id: &str) -> Result<bool, ()> {
+            Ok(self.0.iter().any(|revoked| revoked == revocation_id))
+        }
+    }
+
+    struct Unavailable;
+
+    #[async_trait]
+    impl AsyncRevocationList for Unavailable {
+        async fn is_revoked_async(&self, _revocation_id: &str) -> Result<bool, ()> {
+            Err(())
+        }
+    }
+
+    let issuer = crate::testing::TestIssuer::new();
+    let mut claims = crate::testing::google_id_token_claims(AUDIENCE, "1234", "a@example.com");
+    claims["jti"] = "revoked-jti".into();
+    let revoked = issuer.sign(&claims);
+    claims["jti"] = "other-jti".into();
+    let other = issuer.sign(&claims);
+    assert_eq!(
+        revocation_id(&revoked),
+        "https://accounts.google.com revoked-jti"
+    );
+    assert!(revocation_id("opaque-access-token").starts_with("sha256:"));
+
+    // the same jti of another issuer is another token
+    claims["jti"] = "revoked-jti".into();
+    claims["iss"] = "accounts.google.com".into();
+    let other_issuer = issuer.sign(&claims);
+    assert_ne!(revocation_id(&other_issuer), revocation_id(&revoked));
+    assert_eq!(
+        jwt_revocation_id("https://accounts.google.com", "revoked-jti"),
+        revocation_id(&revoked)
+    );
+
+    let client = AsyncClient::builder(AUDIENCE)
+        .custom_key_provider(issuer.key_provider())
+        .revocation_list(Revoked(vec![
+            "https://accounts.google.com revoked-jti".to_owned()
+        ]))
+        .build();
+    assert_eq!(
+        client.verify_id_token_async(&revoked).await.map(|_| ()),
+        Err(Error::Revoked)
+    );
+    assert!(client.verify_id_token_async(&other).await.is_ok());
+    assert!(client.verify_id_token_async(&other_issuer).await.is_ok());
+
+    // tokens are rejected when revocation cannot be ruled out
+    let client = AsyncClient::builder(AUDIENCE)
+        .custom_key_provider(issuer.key_provider())
+        .revocation_list(Unavailable)
+        .build();
+    assert_eq!(
+        client.verify_id_token_async(&other).await.map(|_| ()),
+        Err(Error::RevocationCheckFailure)
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+pub fn test_token_signer_round_trip() {
+    let ec_key = openssl::ec::EcKey::generate(
+        &openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::X9_62_PRIME256V1).unwrap(),
+    )
+    .unwrap();
+    let ec_pem = ec_key.private_key_to_pem().unwrap();
+    let rsa_pem = openssl::rsa::Rsa::generate(2048)
+        .unwrap()
+        .private_key_to_pem()
+        .unwrap();
+    let claims = serde_json::json!({
+        "iss": "https://app.example.com",
+        "aud": AUDIENCE,
+        "sub": "1234",
+        "iat": 1_000,
+        "exp": 2_000,
+    });
+    let client = |signer: &TokenSigner| {
+        Client::builder(AUDIENCE)
+            .custom_key_provider(signer.key_provider())
+            .issuers(&["https://app.example.com"])
+            .algorithms(&[signer.get_algorithm()])
+            .clock(FixedClock(1_500))
+            .build()
+    };
+
+    for (pem, algorithm) in &[(ec_pem, Algorithm::ES256), (rsa_pem, Algorithm::RS256)] {
+        let signer = TokenSigner::from_pem(pem, "app-key").unwrap();
+        assert_eq!(signer.get_algorithm(), *algorithm);
+        let token = signer.sign(&claims).unwrap();
+        let verified = client(&signer)
+            .verify_token_with_payload::<serde_json::Value>(&token)
+            .unwrap();
+        assert_eq!(verified.get_claims().get_subject(), "1234");
+        assert_eq!(
+            decode_unverified(&token).unwrap().get_header()["kid"],
+            "app-key"
+        );
+    }
+
+    // another signer's key with the same kid is not accepted
+    let signer = TokenSigner::generate("app-key").unwrap();
+    let other = TokenSigner::generate("app-key").unwrap();
+    assert_eq!(
+        client(&signer)
+            .verify_token_with_payload::<serde_json::Value>(&other.sign(&claims).unwrap())
+            .map(|_| ()),
+        Err(Error::InvalidSignature)
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_tokeninfo_client() {
+    let (base_url, _) = serve(|_, path, _| {
+        let audience = if path == "/other-audience" {
+            "another client"
+        } else {
+            AUDIENCE
+        };
+        match path {
+            "/rejected" => Canned {
+                status: "400 Bad Request",
+                cache_control: "no-cache",
+                body: r#"{"error":"invalid_token","error_description":"Invalid Value"}"#.to_owned(),
+            },
+            _ => Canned::json(
+                serde_json::json!({
+                    "azp": audience,
+                    "aud": audience,
+                    "sub": "107067361503954474488",
+                    "scope": "openid https://www.googleapis.com/auth/userinfo.email",
+                    "exp": TOKEN_EXPIRES_AT.to_string(),
+                    "expires_in": "3599",
+                    "email": "fuchsnj@gmail.com",
+                    "email_verified": "true",
+                })
+                .to_string(),
+            ),
+        }
+    });
+    let client = |path: &str| {
+        TokenInfoClient::builder(AUDIENCE)
+            .tokeninfo_url(&format!("{}{}", base_url, path))
+            .require_scope("openid")
+            .clock(FixedClock(TOKEN_EXPIRES_AT - 10))
+    };
+
+    let info = client("/valid")
+        .build()
+        .verify_access_token_async("ya29.token")
+        .await
+        .unwrap();
+    assert_eq!(info.get_subject(), Some("107067361503954474488".to_owned()));
+    assert_eq!(info.get_expires_at(), TOKEN_EXPIRES_AT);
+    assert!(info.is_email_verified());
+    assert!(info.has_scope("https://www.googleapis.com/auth/userinfo.email"));
+
+    assert_eq!(
+        client("/valid")
+            .require_scope("https://www.googleapis.com/auth/drive")
+            .build()
+            .verify_access_token_async("ya29.token")
+            .await
+            .map(|_| ()),
+        Err(Error::MissingScope(
+            "https://www.googleapis.com/auth/drive".to_owned()
+        ))
+    );
+    assert_eq!(
+        client("/valid")
+            .clock(FixedClock(TOKEN_EXPIRES_AT + 1))
+            .build()
+            .verify_access_token_async("ya29.token")
+            .await
+            .map(|_| ()),
+        Err(Error::Expired)
+    );
+    assert_eq!(
+        client("/other-audience")
+            .build()
+            .verify_access_token_async("ya29.token")
+            .await
+            .map(|_| ()),
+        Err(Error::InvalidAudience(vec!["another client".to_owned()]))
+    );
+    assert_eq!(
+        client("/rejected")
+            .build()
+            .verify_access_token_async("ya29.token")
+            .await
+            .map(|_| ()),
+        Err(Error::InvalidAccessToken("Invalid Value".to_owned()))
+    );
+}
@@ -1,4 +1,7 @@
+use serde::Deserializer;
 use serde_derive::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
 
 pub struct Token<P> {
     required_claims: RequiredClaims,
@@ -29,16 +32,19 @@ pub struct RequiredClaims {
     subject: String,
 
     #[serde(rename = "aud")]
-    audience: String,
+    audience: Audience,
 
-    #[serde(rename = "azp")]
-    android_audience: String,
+    #[serde(rename = "azp", default)]
+    authorized_party: Option<String>,
 
     #[serde(rename = "iat")]
     issued_at: u64,
 
     #[serde(rename = "exp")]
     expires_at: u64,
+
+    #[serde(rename = "nbf", default)]
+    not_before: Option<u64>,
 }
 
 impl RequiredClaims {
@@ -49,10 +55,22 @@ impl RequiredClaims {
         self.subject.clone()
     }
     pub fn get_audience(&self) -> String {
-        self.audience.clone()
+        match self.audience {
+            Audience::Single(ref audience) => audience.clone(),
+            Audience::Multiple(ref audiences) => audiences.first().cloned().unwrap_or_default(),
+        }
+    }
+    pub fn get_audiences(&self) -> Vec<String> {
+        match self.audience {
+            Audience::Single(ref audience) => vec![audience.clone()],
+            Audience::Multiple(ref audiences) => audiences.clone(),
+        }
+    }
+    pub fn get_authorized_party(&self) -> Option<String> {
+        self.authorized_party.clone()
     }
     pub fn get_android_audience(&self) -> String {
-        self.android_audience.clone()
+        self.authorized_party.clone().unwrap_or_default()
     }
     pub fn get_issued_at(&self) -> u64 {
         self.issued_at
@@ -60,18 +78,58 @@ impl RequiredClaims {
     pub fn get_expires_at(&self) -> u64 {
         self.expires_at
     }
+    pub fn get_not_before(&self) -> Option<u64> {
+        self.not_before
+    }
+}
+
+/// The `aud` claim may be a single string or an array of strings.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
 }
 
 #[derive(Deserialize, Clone)]
 pub struct IdPayload {
     email: String,
     email_verified: bool,
-    name: String,
-    picture: String,
-    given_name: String,
-    family_name: String,
-    locale: String,
+    // profile claims depend on the requested scopes and are missing for some accounts
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    picture: Option<String>,
+    #[serde(default)]
+    given_name: Option<String>,
+    #[serde(default)]
+    family_name: Option<String>,
+    #[serde(default)]
+    locale: Option<String>,
     hd: Option<String>,
+    #[serde(default)]
+    jti: Option<String>,
+    #[serde(default)]
+    nonce: Option<String>,
+    #[serde(default)]
+    auth_time: Option<u64>,
+    #[serde(default)]
+    amr: Option<Vec<String>>,
+    #[serde(flatten, deserialize_with = "deserialize_extra_claims")]
+    extra: HashMap<String, Value>,
+}
+
+/// Claims that are exposed through `RequiredClaims` or checked during validation, and
+/// therefore left out of the extra claims.
+const REGISTERED_CLAIMS: &[&str] = &["iss", "sub", "aud", "azp", "iat", "exp", "nbf", "at_hash"];
+
+fn deserialize_extra_claims<'de, D>(deserializer: D) -> Result<HashMap<String, Value>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut claims: HashMap<String, Value> = serde::Deserialize::deserialize(deserializer)?;
+    claims.retain(|name, _| !REGISTERED_CLAIMS.contains(&name.as_str()));
+    Ok(claims)
 }
 
 impl IdPayload {
@@ -81,22 +139,43 @@ impl IdPayload {
     pub fn is_email_verified(&self) -> bool {
         self.email_verified
     }
-    pub fn get_name(&self) -> String {
+    pub fn get_name(&self) -> Option<String> {
         self.name.clone()
     }
-    pub fn get_picture_url(&self) -> String {
+    pub fn get_picture_url(&self) -> Option<String> {
         self.picture.clone()
     }
-    pub fn get_given_name(&self) -> String {
+    pub fn get_given_name(&self) -> Option<String> {
         self.given_name.clone()
     }
-    pub fn get_family_name(&self) -> String {
+    pub fn get_family_name(&self) -> Option<String> {
         self.family_name.clone()
     }
-    pub fn get_locale(&self) -> String {
+    pub fn get_locale(&self) -> Option<String> {
         self.locale.clone()
     }
     pub fn get_domain(&self) -> Option<String> {
         self.hd.clone()
     }
+    pub fn get_jwt_id(&self) -> Option<String> {
+        self.jti.clone()
+    }
+    pub fn get_nonce(&self) -> Option<String> {
+        self.nonce.clone()
+    }
+    /// When the user last authenticated, as a unix timestamp.
+    pub fn get_auth_time(&self) -> Option<u64> {
+        self.auth_time
+    }
+    /// The authentication methods used, e.g. `pwd` or `mfa`.
+    pub fn get_authentication_methods(&self) -> Option<Vec<String>> {
+        self.amr.clone()
+    }
+    /// Claims this payload has no field for, such as provider specific ones.
+    pub fn get_extra_claims(&self) -> &HashMap<String, Value> {
+        &self.extra
+    }
+    pub fn get_extra_claim(&self, name: &str) -> Option<&Value> {
+        self.extra.get(name)
+    }
 }
@@ -0,0 +1,207 @@
+use crate::clock::{Clock, SystemClock};
+use crate::error::Error;
+use serde::{Deserialize as _, Deserializer};
+use serde_derive::Deserialize;
+use std::sync::Arc;
+
+pub const GOOGLE_TOKENINFO_URL: &str = "https://oauth2.googleapis.com/tokeninfo";
+
+/// What a tokeninfo endpoint reports about a valid access token.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AccessTokenInfo {
+    #[serde(rename = "aud")]
+    audience: String,
+    #[serde(rename = "azp", default)]
+    authorized_party: Option<String>,
+    #[serde(rename = "sub", default)]
+    subject: Option<String>,
+    #[serde(default)]
+    scope: String,
+    #[serde(rename = "exp", deserialize_with = "deserialize_number")]
+    expires_at: u64,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_bool")]
+    email_verified: Option<bool>,
+}
+
+impl AccessTokenInfo {
+    pub fn get_audience(&self) -> String {
+        self.audience.clone()
+    }
+    pub fn get_authorized_party(&self) -> Option<String> {
+        self.authorized_party.clone()
+    }
+    /// The Google user ID, present when the token was granted an identity scope.
+    pub fn get_subject(&self) -> Option<String> {
+        self.subject.clone()
+    }
+    pub fn get_scopes(&self) -> Vec<String> {
+        self.scope.split_whitespace().map(str::to_owned).collect()
+    }
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .split_whitespace()
+            .any(|granted| granted == scope)
+    }
+    pub fn get_expires_at(&self) -> u64 {
+        self.expires_at
+    }
+    pub fn get_email(&self) -> Option<String> {
+        self.email.clone()
+    }
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified.unwrap_or(false)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenInfoError {
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// Google's tokeninfo endpoint reports numbers and booleans as strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Lenient<T> {
+    Value(T),
+    Text(String),
+}
+
+fn deserialize_number<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Lenient::<u64>::deserialize(deserializer)? {
+        Lenient::Value(number) => Ok(number),
+        Lenient::Text(text) => text.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+fn deserialize_optional_bool<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Lenient::<bool>::deserialize(deserializer)? {
+        Lenient::Value(value) => Ok(Some(value)),
+        Lenient::Text(text) => text.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+pub struct TokenInfoClientBuilder {
+    url: String,
+    audiences: Vec<String>,
+    scopes: Vec<String>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TokenInfoClientBuilder {
+    pub fn new(client_id: &str) -> TokenInfoClientBuilder {
+        TokenInfoClientBuilder::with_audiences(&[client_id])
+    }
+    /// Accepts access tokens issued to any of the given client IDs.
+    pub fn with_audiences<S: AsRef<str>>(audiences: &[S]) -> TokenInfoClientBuilder {
+        TokenInfoClientBuilder {
+            url: GOOGLE_TOKENINFO_URL.to_owned(),
+            audiences: audiences.iter().map(|a| a.as_ref().to_owned()).collect(),
+            scopes: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+    /// Points the client at another tokeninfo endpoint, e.g. a stand-in in tests.
+    pub fn tokeninfo_url(mut self, url: &str) -> Self {
+        self.url = url.to_owned();
+        self
+    }
+    pub fn audience(mut self, client_id: &str) -> Self {
+        if !self.audiences.iter().any(|audience| audience == client_id) {
+            self.audiences.push(client_id.to_owned());
+        }
+        self
+    }
+    /// Requires the access token to have been granted `scope`. May be called multiple
+    /// times to require several scopes.
+    pub fn require_scope(mut self, scope: &str) -> Self {
+        self.scopes.push(scope.to_owned());
+        self
+    }
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+    pub fn build(self) -> TokenInfoClient {
+        TokenInfoClient {
+            url: self.url,
+            audiences: self.audiences,
+            scopes: self.scopes,
+            clock: self.clock,
+        }
+    }
+}
+
+/// Validates OAuth access tokens, which are opaque to their holder, by asking the
+/// issuer's tokeninfo endpoint about them.
+#[derive(Clone)]
+pub struct TokenInfoClient {
+    url: String,
+    audiences: Vec<String>,
+    scopes: Vec<String>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TokenInfoClient {
+    pub fn builder(client_id: &str) -> TokenInfoClientBuilder {
+        TokenInfoClientBuilder::new(client_id)
+    }
+    pub fn builder_with_audiences<S: AsRef<str>>(audiences: &[S]) -> TokenInfoClientBuilder {
+        TokenInfoClientBuilder::with_audiences(audiences)
+    }
+
+    pub async fn verify_access_token_async(
+        &self,
+        access_token: &str,
+    ) -> Result<AccessTokenInfo, Error> {
+        // the token is posted rather than put in the query string, which ends up in logs
+        let response = reqwest::Client::new()
+            .post(&self.url)
+            .form(&[("access_token", access_token)])
+            .send()
+            .await
+            .map_err(|_| Error::IntrospectionFailure)?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|_| Error::IntrospectionFailure)?;
+        if status.is_client_error() {
+            let rejection: TokenInfoError =
+                serde_json::from_str(&text).map_err(|_| Error::IntrospectionFailure)?;
+            return Err(Error::InvalidAccessToken(
+                rejection.error_description.unwrap_or(rejection.error),
+            ));
+        }
+        if !status.is_success() {
+            return Err(Error::IntrospectionFailure);
+        }
+        let info: AccessTokenInfo =
+            serde_json::from_str(&text).map_err(|_| Error::IntrospectionFailure)?;
+        self.validate(&info)?;
+        Ok(info)
+    }
+
+    fn validate(&self, info: &AccessTokenInfo) -> Result<(), Error> {
+        if !self.audiences.contains(&info.audience) {
+            return Err(Error::InvalidAudience(vec![info.audience.clone()]));
+        }
+        if info.expires_at < self.clock.timestamp() {
+            return Err(Error::Expired);
+        }
+        match self.scopes.iter().find(|scope| !info.has_scope(scope)) {
+            Some(scope) => Err(Error::MissingScope(scope.clone())),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,23 +1,73 @@
-use std::{
-    sync::{Arc, Mutex},
-    time::{SystemTime, UNIX_EPOCH},
-};
-
-use tokio::sync::Mutex as AsyncMutex;
+#[cfg(feature = "blocking")]
+use std::sync::{Arc, Mutex};
 
 use serde::Deserialize;
+use serde_json::Value;
 
 #[cfg(feature = "async")]
 use crate::key_provider::AsyncKeyProvider;
 #[cfg(feature = "blocking")]
 use crate::key_provider::KeyProvider;
-use crate::{base64_decode, header::Header, jwk::JsonWebKey, Error, RequiredClaims, Token};
+use crate::{
+    algorithm::Algorithm,
+    base64_decode,
+    binding::{BindingClaims, TokenBinding},
+    error::Segment,
+    header::Header,
+    jwk::JsonWebKey,
+    validation::Validation,
+    Error, RequiredClaims, Token,
+};
+
+fn split_segments(token_string: &str) -> Result<(&str, &str, &str), Error> {
+    let mut segments = token_string.split('.');
+    match (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) {
+        (Some(header), Some(payload), Some(signature), None) => Ok((header, payload, signature)),
+        _ => Err(Error::MalformedToken),
+    }
+}
+
+/// The header and claims of a token, decoded without checking its signature or any claim.
+#[derive(Clone, Debug)]
+pub struct DecodedToken {
+    header: Value,
+    claims: Value,
+}
+
+impl DecodedToken {
+    pub fn get_header(&self) -> &Value {
+        &self.header
+    }
+    pub fn get_claims(&self) -> &Value {
+        &self.claims
+    }
+}
+
+/// Decodes a token for inspection, e.g. to find out why it fails verification. Nothing in
+/// the result can be trusted.
+pub fn decode_unverified(token_string: &str) -> Result<DecodedToken, Error> {
+    let (encoded_header, encoded_payload, _) = split_segments(token_string)?;
+    let header =
+        base64_decode(encoded_header).map_err(|e| Error::MalformedSegment(Segment::Header, e))?;
+    let header = serde_json::from_slice(&header).map_err(Error::InvalidHeader)?;
+    let payload =
+        base64_decode(encoded_payload).map_err(|e| Error::MalformedSegment(Segment::Payload, e))?;
+    let claims = serde_json::from_slice(&payload).map_err(Error::InvalidPayload)?;
+    Ok(DecodedToken { header, claims })
+}
 
 pub struct UnverifiedToken<P> {
     header: Header,
+    algorithm: Algorithm,
     signed_body: String,
     signature: Vec<u8>,
     claims: RequiredClaims,
+    binding_claims: BindingClaims,
     json_payload: P,
 }
 
@@ -25,50 +75,40 @@ impl<P> UnverifiedToken<P>
 where
     for<'a> P: Deserialize<'a>,
 {
-    pub fn validate(
-        token_string: &str,
-        check_expiration: bool,
-        client_id: &str,
-    ) -> Result<Self, Error> {
-        let mut segments = token_string.split('.');
-        let encoded_header = segments.next().ok_or(Error::InvalidToken)?;
-        let encoded_payload = segments.next().ok_or(Error::InvalidToken)?;
-        let encoded_signature = segments.next().ok_or(Error::InvalidToken)?;
-
-        let header: Header = serde_json::from_slice(&base64_decode(&encoded_header)?)?;
+    pub fn validate(token_string: &str, validation: &Validation) -> Result<Self, Error> {
+        let (encoded_header, encoded_payload, encoded_signature) = split_segments(token_string)?;
+        let header = base64_decode(encoded_header)
+            .map_err(|e| Error::MalformedSegment(Segment::Header, e))?;
+        let header: Header = serde_json::from_slice(&header).map_err(Error::InvalidHeader)?;
+        let algorithm = validation.validate_header(&header)?;
         let signed_body = format!("{}.{}", encoded_header, encoded_payload);
-        let signature = base64_decode(&encoded_signature)?;
-        let payload = base64_decode(&encoded_payload)?;
-        let claims: RequiredClaims = serde_json::from_slice(&payload)?;
-        if claims.get_audience() != client_id {
-            return Err(Error::InvalidToken);
-        }
-        let issuer = claims.get_issuer();
-        if issuer != "https://accounts.google.com" && issuer != "accounts.google.com" {
-            return Err(Error::InvalidToken);
-        }
-        let current_timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        if check_expiration && claims.get_expires_at() < current_timestamp {
-            return Err(Error::Expired);
-        }
-        if claims.get_issued_at() > claims.get_expires_at() {
-            return Err(Error::InvalidToken);
-        }
-        let json_payload: P = serde_json::from_slice(&payload)?;
+        let signature = base64_decode(encoded_signature)
+            .map_err(|e| Error::MalformedSegment(Segment::Signature, e))?;
+        let payload = base64_decode(encoded_payload)
+            .map_err(|e| Error::MalformedSegment(Segment::Payload, e))?;
+        let claims: RequiredClaims =
+            serde_json::from_slice(&payload).map_err(Error::InvalidPayload)?;
+        validation.validate_claims(&claims)?;
+        validation.validate_hosted_domain(&payload)?;
+        let binding_claims: BindingClaims =
+            serde_json::from_slice(&payload).map_err(Error::InvalidPayload)?;
+        let json_payload: P = serde_json::from_slice(&payload).map_err(Error::InvalidPayload)?;
         Ok(Self {
             claims,
+            binding_claims,
             signature,
             signed_body,
             json_payload,
             header,
+            algorithm,
         })
     }
 }
 
 impl<P> UnverifiedToken<P> {
+    pub fn validate_binding(&self, binding: &TokenBinding) -> Result<(), Error> {
+        self.binding_claims.validate(self.algorithm, binding)
+    }
     #[cfg(feature = "blocking")]
     pub fn verify<KP: KeyProvider>(self, key_provider: &Arc<Mutex<KP>>) -> Result<Token<P>, Error> {
         let key_id = self.header.key_id.clone();
@@ -77,28 +117,19 @@ impl<P> UnverifiedToken<P> {
     #[cfg(feature = "async")]
     pub async fn verify_async<KP: AsyncKeyProvider>(
         self,
-        key_provider: &Arc<Mutex<KP>>,
-    ) -> Result<Token<P>, Error> {
-        let key_id = self.header.key_id.clone();
-        self.verify_with_key(key_provider.lock().unwrap().get_key_async(&key_id).await)
-    }
-
-    #[cfg(feature = "async")]
-    pub async fn verify_async_mutex<KP: AsyncKeyProvider>(
-        self,
-        key_provider: &Arc<AsyncMutex<KP>>,
+        key_provider: &KP,
     ) -> Result<Token<P>, Error> {
         let key_id = self.header.key_id.clone();
-        self.verify_with_key(key_provider.lock().await.get_key_async(&key_id).await)
+        self.verify_with_key(key_provider.get_key_async(&key_id).await)
     }
 
     fn verify_with_key(self, key: Result<Option<JsonWebKey>, ()>) -> Result<Token<P>, Error> {
         let key = match key {
             Ok(Some(key)) => key,
-            Ok(None) => return Err(Error::InvalidToken),
+            Ok(None) => return Err(Error::UnknownKeyId(self.header.key_id)),
             Err(_) => return Err(Error::RetrieveKeyFailure),
         };
-        key.verify(self.signed_body.as_bytes(), &self.signature)?;
+        key.verify(self.algorithm, self.signed_body.as_bytes(), &self.signature)?;
         Ok(Token::new(self.claims, self.json_payload))
     }
 }
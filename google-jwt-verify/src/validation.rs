@@ -0,0 +1,182 @@
+use crate::algorithm::{Algorithm, ASYMMETRIC_ALGORITHMS};
+use crate::clock::{Clock, SystemClock};
+use crate::error::Error;
+use crate::header::Header;
+use crate::token::RequiredClaims;
+use serde_derive::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub(crate) const GOOGLE_ISSUERS: &[&str] = &["https://accounts.google.com", "accounts.google.com"];
+
+/// Claim checks applied to a token before its signature is verified.
+#[derive(Clone)]
+pub(crate) struct Validation {
+    audiences: Vec<String>,
+    issuers: Vec<String>,
+    check_authorized_party: bool,
+    check_expiration: bool,
+    check_not_before: bool,
+    leeway: u64,
+    max_age: Option<u64>,
+    clock: Arc<dyn Clock>,
+    hosted_domains: Vec<String>,
+    algorithms: Vec<Algorithm>,
+}
+
+#[derive(Deserialize)]
+struct HostedDomainClaim {
+    hd: Option<String>,
+}
+
+impl Validation {
+    pub fn new<S: AsRef<str>>(audiences: &[S]) -> Validation {
+        Validation {
+            audiences: audiences.iter().map(|a| a.as_ref().to_owned()).collect(),
+            issuers: GOOGLE_ISSUERS.iter().map(|i| i.to_string()).collect(),
+            check_authorized_party: false,
+            check_expiration: true,
+            check_not_before: false,
+            leeway: 0,
+            max_age: None,
+            clock: Arc::new(SystemClock),
+            hosted_domains: Vec::new(),
+            algorithms: ASYMMETRIC_ALGORITHMS.to_vec(),
+        }
+    }
+
+    pub fn add_audience(&mut self, audience: &str) {
+        if !self.is_allowed_audience(audience) {
+            self.audiences.push(audience.to_owned());
+        }
+    }
+
+    pub fn set_issuers<S: AsRef<str>>(&mut self, issuers: &[S]) {
+        self.issuers = issuers.iter().map(|i| i.as_ref().to_owned()).collect();
+    }
+
+    pub fn set_check_authorized_party(&mut self, check: bool) {
+        self.check_authorized_party = check;
+    }
+
+    pub fn set_check_expiration(&mut self, check: bool) {
+        self.check_expiration = check;
+    }
+
+    pub fn set_check_not_before(&mut self, check: bool) {
+        self.check_not_before = check;
+    }
+
+    pub fn set_leeway(&mut self, leeway: Duration) {
+        self.leeway = leeway.as_secs();
+    }
+
+    pub fn set_max_age(&mut self, max_age: Duration) {
+        self.max_age = Some(max_age.as_secs());
+    }
+
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Arc::new(clock);
+    }
+
+    pub fn set_algorithms(&mut self, algorithms: &[Algorithm]) {
+        self.algorithms = algorithms.to_vec();
+    }
+
+    pub fn add_hosted_domain(&mut self, domain: &str) {
+        if !self.hosted_domains.iter().any(|allowed| allowed == domain) {
+            self.hosted_domains.push(domain.to_owned());
+        }
+    }
+
+    fn is_allowed_audience(&self, audience: &str) -> bool {
+        self.audiences.iter().any(|allowed| allowed == audience)
+    }
+
+    pub fn validate_claims(&self, claims: &RequiredClaims) -> Result<(), Error> {
+        let audiences = claims.get_audiences();
+        if !audiences
+            .iter()
+            .any(|audience| self.is_allowed_audience(audience))
+        {
+            return Err(Error::InvalidAudience(audiences));
+        }
+        if self.check_authorized_party {
+            match claims.get_authorized_party() {
+                Some(ref azp) if self.is_allowed_audience(azp) => (),
+                azp => return Err(Error::InvalidAuthorizedParty(azp)),
+            }
+        }
+        let issuer = claims.get_issuer();
+        if !self.issuers.contains(&issuer) {
+            return Err(Error::InvalidIssuer(issuer));
+        }
+        let now = self.clock.timestamp();
+        if self.check_expiration && claims.get_expires_at().saturating_add(self.leeway) < now {
+            return Err(Error::Expired);
+        }
+        if self.check_not_before {
+            if let Some(not_before) = claims.get_not_before() {
+                if not_before > now.saturating_add(self.leeway) {
+                    return Err(Error::NotYetValid);
+                }
+            }
+        }
+        if let Some(max_age) = self.max_age {
+            let issued_at = claims.get_issued_at();
+            if issued_at > now.saturating_add(self.leeway) {
+                return Err(Error::NotYetValid);
+            }
+            if issued_at
+                .saturating_add(max_age)
+                .saturating_add(self.leeway)
+                < now
+            {
+                return Err(Error::Expired);
+            }
+        }
+        if claims.get_issued_at() > claims.get_expires_at() {
+            return Err(Error::InvalidToken);
+        }
+        Ok(())
+    }
+
+    /// Checks that the user signed in no later than now, allowing for the leeway.
+    pub fn validate_auth_time(&self, auth_time: u64) -> Result<(), Error> {
+        if auth_time > self.clock.timestamp().saturating_add(self.leeway) {
+            return Err(Error::InvalidAuthTime);
+        }
+        Ok(())
+    }
+
+    /// Checks the `hd` claim of the raw payload against the allowed hosted domains.
+    /// Nothing is enforced while no hosted domain has been configured.
+    pub fn validate_hosted_domain(&self, payload: &[u8]) -> Result<(), Error> {
+        if self.hosted_domains.is_empty() {
+            return Ok(());
+        }
+        let claim: HostedDomainClaim =
+            serde_json::from_slice(payload).map_err(Error::InvalidPayload)?;
+        match claim.hd {
+            Some(ref hd) if self.hosted_domains.iter().any(|allowed| allowed == hd) => Ok(()),
+            _ => Err(Error::InvalidHostedDomain),
+        }
+    }
+
+    /// Checks `typ` and returns the header's algorithm if it is allowed. Symmetric
+    /// algorithms are always rejected: a verifier holding only public keys would
+    /// otherwise be open to tokens "signed" with the public key as HMAC secret.
+    pub fn validate_header(&self, header: &Header) -> Result<Algorithm, Error> {
+        if let Some(ref token_type) = header.token_type {
+            if !token_type.eq_ignore_ascii_case("JWT") {
+                return Err(Error::InvalidTokenType(token_type.clone()));
+            }
+        }
+        match header.algorithm.parse::<Algorithm>() {
+            Ok(algorithm) if !algorithm.is_symmetric() && self.algorithms.contains(&algorithm) => {
+                Ok(algorithm)
+            }
+            _ => Err(Error::DisallowedAlgorithm(header.algorithm.clone())),
+        }
+    }
+}
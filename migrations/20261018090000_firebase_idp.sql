@@ -0,0 +1,2 @@
+-- Firebase Authentication users are identified by their Firebase user ID
+ALTER TYPE USER_EXTERNAL_IDP ADD VALUE IF NOT EXISTS 'Firebase';
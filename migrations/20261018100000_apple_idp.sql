@@ -0,0 +1,2 @@
+-- Sign in with Apple users are identified by their Apple user ID (the identity token's sub)
+ALTER TYPE USER_EXTERNAL_IDP ADD VALUE IF NOT EXISTS 'Apple';
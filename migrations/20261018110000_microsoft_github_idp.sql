@@ -0,0 +1,3 @@
+-- Microsoft users are identified by the ID token's sub, GitHub users by their numeric user ID
+ALTER TYPE USER_EXTERNAL_IDP ADD VALUE IF NOT EXISTS 'Microsoft';
+ALTER TYPE USER_EXTERNAL_IDP ADD VALUE IF NOT EXISTS 'GitHub';
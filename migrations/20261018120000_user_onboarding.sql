@@ -0,0 +1,2 @@
+-- users created on their first login pick their sign during onboarding
+ALTER TABLE USERS ALTER COLUMN SIGN_ID DROP NOT NULL;
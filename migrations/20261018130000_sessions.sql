@@ -0,0 +1,22 @@
+-- sessions opened through POST /auth/session, each renewed with a rotating refresh token
+create table SESSIONS (
+    ID uuid default uuid_generate_v4(),
+    USER_ID uuid not null,
+    EXPIRES_AT timestamp not null,
+    REVOKED_AT timestamp,
+    CREATED_AT timestamp not null default current_timestamp,
+    UPDATED_AT timestamp not null default current_timestamp,
+    PRIMARY KEY(ID)
+);
+ALTER TABLE SESSIONS ADD CONSTRAINT SESSIONS_USER_ID_FKEY FOREIGN KEY (USER_ID) REFERENCES USERS(ID) ON DELETE CASCADE;
+
+-- only the SHA-256 hash of a refresh token is stored. A token is used once; presenting a used
+-- token again means it leaked, and revokes the whole session
+create table SESSION_REFRESH_TOKENS (
+    TOKEN_HASH varchar not null,
+    SESSION_ID uuid not null,
+    USED_AT timestamp,
+    CREATED_AT timestamp not null default current_timestamp,
+    PRIMARY KEY(TOKEN_HASH)
+);
+ALTER TABLE SESSION_REFRESH_TOKENS ADD CONSTRAINT SESSION_REFRESH_TOKENS_SESSION_ID_FKEY FOREIGN KEY (SESSION_ID) REFERENCES SESSIONS(ID) ON DELETE CASCADE;
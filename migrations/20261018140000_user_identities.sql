@@ -0,0 +1,20 @@
+-- the identity provider accounts a user signs in with. USERS.EXTERNAL_IDP and EXTERNAL_IDP_ID keep the
+-- account the user signed up with, which may since have been unlinked
+create table USER_IDENTITIES (
+    PROVIDER USER_EXTERNAL_IDP not null,
+    SUBJECT varchar not null,
+    USER_ID uuid not null,
+    LINKED_AT timestamp not null default current_timestamp,
+    PRIMARY KEY(PROVIDER, SUBJECT)
+);
+ALTER TABLE USER_IDENTITIES ADD CONSTRAINT USER_IDENTITIES_USER_ID_FKEY FOREIGN KEY (USER_ID) REFERENCES USERS(ID) ON DELETE CASCADE;
+CREATE INDEX IF NOT EXISTS USER_IDENTITIES_USER_ID_IDX ON USER_IDENTITIES (USER_ID);
+
+-- every existing user's account becomes their first identity
+INSERT INTO USER_IDENTITIES (PROVIDER, SUBJECT, USER_ID, LINKED_AT)
+    SELECT EXTERNAL_IDP, EXTERNAL_IDP_ID, ID, CREATED_AT FROM USERS
+    ON CONFLICT DO NOTHING;
+
+-- the identity each session was opened with, so that unlinking the identity ends its sessions
+ALTER TABLE SESSIONS ADD COLUMN PROVIDER USER_EXTERNAL_IDP;
+ALTER TABLE SESSIONS ADD COLUMN SUBJECT varchar;
@@ -0,0 +1,7 @@
+-- the todo example module had no table of its own, so its queries could not be checked at build time
+create table if not exists TODOS (
+    ID SERIAL,
+    DESCRIPTION varchar not null,
+    DONE boolean not null default false,
+    PRIMARY KEY(ID)
+);
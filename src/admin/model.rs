@@ -0,0 +1,60 @@
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::web::Data;
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::AuthenticatedUser, errors::AppError, redis::revoke_token, InternalAppData};
+
+// this struct will use to receive user input
+#[derive(Serialize, Deserialize)]
+pub struct RevocationRequest {
+    // the token's iss and jti, or the token itself for tokens without a jti
+    pub issuer: Option<String>,
+    pub jti: Option<String>,
+    pub token: Option<String>,
+    // the token's exp; the deny-list entry is dropped once the token has expired anyway
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Revocation {
+    pub revocation_id: String,
+    pub expires_at: u64,
+}
+
+impl Revocation {
+    pub async fn create(
+        user: AuthenticatedUser,
+        request: RevocationRequest,
+        internal_app_data: &InternalAppData,
+        redis: &Data<Addr<RedisActor>>,
+    ) -> Result<Revocation, AppError> {
+        // only the sysadmin: every Google Workspace login is marked internal, so being internal
+        // says nothing about being allowed to lock others out
+        if user.user.email.as_ref() != Some(&internal_app_data.sysadmin) {
+            debug!("User {} is not allowed to revoke tokens", user.user.id);
+            return Err(AppError::NOT_AUTHORIZED.into());
+        }
+
+        let revocation_id = match (request.issuer, request.jti, request.token) {
+            (Some(issuer), Some(jti), None) if !jti.is_empty() => {
+                google_jwt_verify::jwt_revocation_id(&issuer, &jti)
+            }
+            (None, None, Some(token)) if !token.is_empty() => {
+                google_jwt_verify::revocation_id(&token)
+            }
+            _ => {
+                return Err(AppError::INVALID_INPUT.message(
+                    "Provide either the token's issuer and jti, or the token".to_string(),
+                ))
+            }
+        };
+
+        revoke_token(&revocation_id, request.expires_at, redis).await?;
+
+        Ok(Revocation {
+            revocation_id,
+            expires_at: request.expires_at,
+        })
+    }
+}
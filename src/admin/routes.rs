@@ -0,0 +1,40 @@
+use crate::{
+    admin::{Revocation, RevocationRequest},
+    auth::AuthenticatedUser,
+    InternalAppData,
+};
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+
+// revoke a token before it expires
+#[post("/admin/revocations")]
+async fn create_revocation(
+    user: AuthenticatedUser,
+    request: web::Json<RevocationRequest>,
+    internal_app_data: web::Data<InternalAppData>,
+    redis: web::Data<Addr<RedisActor>>,
+) -> impl Responder {
+    let result = Revocation::create(
+        user,
+        request.into_inner(),
+        internal_app_data.get_ref(),
+        &redis,
+    )
+    .await;
+
+    match result {
+        Ok(revocation) => HttpResponse::Ok().json(revocation),
+        Err(e) => {
+            debug!(
+                "Error occurred in Admin >> routes.rs create_revocation function: \n{}",
+                e
+            );
+            e.error_response()
+        }
+    }
+}
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_revocation);
+}
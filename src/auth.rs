@@ -14,10 +14,10 @@ use actix_redis::RedisActor;
 
 use crate::{
     errors::AppError,
+    idp::IdentityProvider,
     user::{User, UserExternalIDP},
     InternalAppData,
 };
-use actix_http::http::HeaderValue;
 // use actix_web::error::ErrorUnauthorized;
 use actix_web::{dev, web::Data, FromRequest, HttpRequest};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
@@ -25,38 +25,137 @@ use futures::future::{ready, BoxFuture};
 // use futures_util::future::{err, ok, Ready};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use uuid::Uuid;
 
-use crate::redis::{get_redis_key, set_redis_key_with_expiration};
+use crate::redis::{
+    get_redis_key, is_token_revoked, set_redis_key_with_expiration, TokenCacheKeys,
+};
 
+/// The identity provider account a bearer token was issued for. It is cached rather than the
+/// user, and the user looked up through it on every use, so that a token no longer signs in to
+/// a user the identity was unlinked from.
 #[derive(Serialize, Deserialize)]
-pub struct Facebook<T> {
-    pub data: T,
+pub struct TokenIdentity {
+    pub provider: UserExternalIDP,
+    pub subject: String,
 }
 
-// impl From<Facebook<FacebookResponseData>> for RespValue {
-//     fn from(data: Facebook<FacebookResponseData>) -> Self {
-//         let j = serde_json::to_string(&data).unwrap();
-//         let asd = RespValue::SimpleString(j);
-//         asd
-//     }
-// }
-#[derive(Serialize, Deserialize)]
-pub struct FacebookResponseData {
-    pub app_id: String,
-    pub r#type: String,
-    pub application: String,
-    pub data_access_expires_at: u64,
-    pub error: Option<FacebookError>,
-    pub expires_at: u64,
-    pub is_valid: bool,
-    pub scopes: Vec<String>,
-    pub user_id: String,
+/// The Redis key the identity `token` was issued for is cached under.
+pub fn token_cache_key(cache_keys: &TokenCacheKeys, idp: &UserExternalIDP, token: &str) -> String {
+    cache_keys.key(&idp.to_string().to_lowercase(), token)
 }
-#[derive(Serialize, Deserialize)]
-pub struct FacebookError {
-    pub code: u32,
-    pub message: String,
-    pub subcode: u32,
+
+/// Caches the token's identity in Redis until the credential expires. Credentials
+/// accepted within the clock skew leeway may already be past their expiry.
+async fn cache_identity(
+    key: String,
+    identity: &TokenIdentity,
+    expires_at: u64,
+    redis: &Data<Addr<RedisActor>>,
+) -> Result<(), AppError> {
+    let current_timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let key_expire_at_in_seconds = expires_at.saturating_sub(current_timestamp).max(1);
+    let identity_serialized = serde_json::to_string(identity).unwrap();
+    set_redis_key_with_expiration(
+        key,
+        identity_serialized,
+        key_expire_at_in_seconds.to_string(),
+        redis,
+    )
+    .await
+}
+
+/// Rejects bearer tokens on the Redis deny-list. Checked before the user cache, which would
+/// otherwise keep serving a revoked token until it expires. This is the only revocation check
+/// of identity provider tokens, whose clients are built without a revocation list.
+pub async fn ensure_not_revoked(
+    token: &str,
+    redis: &Data<Addr<RedisActor>>,
+) -> Result<(), AppError> {
+    if is_token_revoked(&google_jwt_verify::revocation_id(token), redis).await? {
+        debug!("Revoked token rejected");
+        return Err(AppError::NOT_AUTHORIZED.message("token has been revoked".to_string()));
+    }
+    Ok(())
+}
+
+/// Authenticates a bearer token of any identity provider: the user is looked up by the
+/// provider's subject, created on first login, and the identity cached in Redis until the
+/// token expires.
+pub async fn authenticate(
+    provider: &dyn IdentityProvider,
+    token: &str,
+    cache_keys: &TokenCacheKeys,
+    db_pool: &PgPool,
+    redis: &Data<Addr<RedisActor>>,
+) -> Result<AuthenticatedUser, AppError> {
+    let (user, _) = authenticate_identity(provider, token, cache_keys, db_pool, redis).await?;
+    Ok(user)
+}
+
+/// Like `authenticate`, also returning the identity the token was issued for.
+pub async fn authenticate_identity(
+    provider: &dyn IdentityProvider,
+    token: &str,
+    cache_keys: &TokenCacheKeys,
+    db_pool: &PgPool,
+    redis: &Data<Addr<RedisActor>>,
+) -> Result<(AuthenticatedUser, TokenIdentity), AppError> {
+    ensure_not_revoked(token, redis).await?;
+
+    // keyed by a hash of the token, so that Redis holds no credentials
+    let key = token_cache_key(cache_keys, &provider.idp(), token);
+
+    // we have the token's identity in redis, so the token was verified already. This entry will expire in
+    // redis based on the token expiration data. The user is read fresh, in case it changed, was deleted
+    // or the identity was unlinked
+    if let Some(cached) = get_redis_key::<TokenIdentity>(key.as_str(), redis).await? {
+        if let Some(user) = User::find_by_idp_id(&cached.provider, &cached.subject, db_pool).await?
+        {
+            return Ok((AuthenticatedUser { user }, cached));
+        }
+    }
+
+    // we don't have key in redis, evaluate and store in redis
+    let identity = provider.verify(token).await?;
+    let mut user = match User::find_by_idp_id(&provider.idp(), &identity.subject, db_pool).await? {
+        Some(user) => user,
+        // first login: create the user, who then completes onboarding through /user/onboarding
+        None => {
+            debug!("Provisioning user with IDP: {}", identity.subject);
+            let profile = &identity.profile;
+            // unverified addresses could claim someone else's email, and relay addresses are
+            // not the user's, so neither becomes the account email
+            let email = profile
+                .email
+                .as_deref()
+                .filter(|_| profile.email_verified && !profile.email_is_private_relay);
+            User::provision(
+                provider.idp(),
+                &identity.subject,
+                email,
+                profile.name.as_deref(),
+                profile.is_internal,
+                db_pool,
+            )
+            .await?
+        }
+    };
+    if identity.profile.is_internal && user.is_internal != Some(true) {
+        user = User::mark_internal(user.id, db_pool).await?;
+    }
+
+    // let's save this token's identity in REDIS
+    let token_identity = TokenIdentity {
+        provider: provider.idp(),
+        subject: identity.subject,
+    };
+    cache_identity(key, &token_identity, identity.expires_at, redis).await?;
+
+    Ok((AuthenticatedUser { user }, token_identity))
 }
 
 #[derive(Debug)]
@@ -70,6 +169,46 @@ impl fmt::Display for AuthenticatedUser {
     }
 }
 
+/// The unauthenticated user GET requests run as, cached by ID.
+async fn free_user(
+    internal_app_data: &InternalAppData,
+    db_pool: &PgPool,
+    redis: &Data<Addr<RedisActor>>,
+) -> Result<AuthenticatedUser, AppError> {
+    let key = "free_user".to_string();
+    // only the ID is cached. Entries that held the whole user do not parse as
+    // an ID, and are replaced
+    let free_user = match get_redis_key::<Uuid>(key.as_str(), redis).await? {
+        Some(user_id) => User::find_by_uuid(user_id, db_pool).await?,
+        None => None,
+    };
+
+    match free_user {
+        Some(user) => Ok(AuthenticatedUser { user }),
+        _ => {
+            let user = User::find_by_email_id(&internal_app_data.dummy_user, db_pool)
+                .await?
+                .ok_or_else(|| {
+                    debug!("User '{}' not found", &internal_app_data.sysadmin);
+                    AppError::NOT_AUTHORIZED
+                })?;
+
+            let key_expire_at_in_seconds: u64 = 86600;
+            let user_id_serialized = serde_json::to_string(&user.id).unwrap();
+
+            set_redis_key_with_expiration(
+                key,
+                user_id_serialized,
+                key_expire_at_in_seconds.to_string(),
+                redis,
+            )
+            .await?;
+
+            Ok(AuthenticatedUser { user })
+        }
+    }
+}
+
 impl FromRequest for AuthenticatedUser {
     type Error = AppError;
     // type Future = Ready<Result<Self, Self::Error>>;
@@ -85,191 +224,59 @@ impl FromRequest for AuthenticatedUser {
         // debug!("{:?}", internal_app_data);
 
         // check header to identify IDP:
-        let idp = req.headers().get("idp");
-
-        // create a dummy header value to use as Err later on
-        let none_header = HeaderValue::from_static("hello");
-        let idp = idp.unwrap_or_else(|| &none_header);
-
-        let idp = if idp.to_str().unwrap() == "Google" {
-            Ok(UserExternalIDP::Google)
-        } else if idp.to_str().unwrap() == "Facebook" {
-            Ok(UserExternalIDP::Facebook)
-        } else {
-            Err("Invalid IDP provided")
-        };
+        let has_idp_header = req.headers().contains_key("idp");
+        let provider = req
+            .headers()
+            .get("idp")
+            .and_then(|idp| idp.to_str().ok())
+            .and_then(|idp| internal_app_data.identity_providers.get(idp));
 
         // let user_data = req.app_data::<Json<UserTestPayload>>().unwrap().clone();
         let bearer_result = BearerAuth::from_request(req, payload).into_inner();
 
-        let current_timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        match (idp, bearer_result) {
-            //handle Google authentication here
-            (Ok(UserExternalIDP::Google), Ok(bearer)) => {
+        match (provider, bearer_result) {
+            (Some(provider), Ok(bearer)) => {
                 let future = async move {
-                    let key = format!("google-{}", bearer.token());
-
-                    // debug!("google key: {}", key);
-
-                    let google_key = get_redis_key::<User>(key.as_str(), &redis).await?;
-
-                    match google_key {
-                        // we have the user data in redis, let's use that return data. This data will expire in redis
-                        // based on the token expiration data. (hopefully user isn't already deleted from database lol)
-                        Some(user) => {
-                            // let user: User = serde_json::from_str(&key)?;
-                            Ok(AuthenticatedUser { user })
-                        }
-
-                        // we don't have key in redis, evaluate and store in redis
-                        _ => {
-                            let g_client = &internal_app_data.google_client;
-                            let g_data = g_client.verify_id_token_async(bearer.token()).await;
-                            match g_data {
-                                Ok(token) => {
-                                    let user_id = token.get_claims().get_subject();
-                                    let user = User::find_by_idp_id(&user_id, &db_pool)
-                                        .await?
-                                        .ok_or_else(|| {
-                                            debug!("User not found with IDP: {}", user_id);
-                                            AppError::NOT_AUTHORIZED
-                                        })?;
-
-                                    // let's save this user info in REDIS
-                                    let key_expire_at_in_seconds =
-                                        token.get_claims().get_expires_at() - current_timestamp;
-                                    let user_serialized = serde_json::to_string(&user).unwrap();
-
-                                    let _key_set = set_redis_key_with_expiration(
-                                        key,
-                                        user_serialized,
-                                        key_expire_at_in_seconds.to_string(),
-                                        &redis,
-                                    )
-                                    .await?;
-
-                                    Ok(AuthenticatedUser { user })
-                                }
-                                Err(e) => {
-                                    debug!("Error while decoding Google token: {:?}", e);
-                                    Err(AppError::NOT_AUTHORIZED.into())
-                                }
-                            }
-                        }
-                    }
+                    authenticate(
+                        provider.as_ref(),
+                        bearer.token(),
+                        &internal_app_data.token_cache_keys,
+                        &db_pool,
+                        &redis,
+                    )
+                    .await
                 };
 
                 Box::pin(future)
             }
-
-            //handle Facebook authentication here
-            (Ok(UserExternalIDP::Facebook), Ok(bearer)) => {
-                let url = format!(
-                    "https://graph.facebook.com/debug_token?input_token={}&access_token={}",
-                    bearer.token(),
-                    internal_app_data.facebook_data["facebook_access_token"]
-                );
-
+            // without an `idp` header the bearer token is one of our own access tokens, issued
+            // through POST /auth/session. GET requests with any other token, or with an expired
+            // or ended session's, flow through as the free user, as before sessions existed.
+            (None, Ok(bearer)) if !has_idp_header => {
+                let is_get = req.method() == "GET";
                 let future = async move {
-                    let key = format!("facebook-{}", bearer.token());
-
-                    // return type should be a User
-                    let facebook_user = get_redis_key::<User>(key.as_str(), &redis).await?;
-
-                    match facebook_user {
-                        // we have the user data in redis, let's use that return data. This data will expire in redis
-                        // based on the token expiration data. (hopefully user isn't already deleted from database lol)
-                        Some(user) => {
-                            Ok(AuthenticatedUser { user })
-                            /*if let Ok(data) =
-                                serde_json::from_str::<Facebook<FacebookResponseData>>(&fb_user)
-                            {
-                                if data.data.expires_at < current_timestamp {
-                                    // Token is NOT valid.
-                                    Err(AppError::INVALID_CREDENTIALS.into())
-                                } else {
-                                    // Good....the token is valid
-                                    let user = User::find_by_idp_id(&data.data.user_id, &db_pool)
-                                        .await?
-                                        .ok_or_else(|| {
-                                            debug!(
-                                                "User not found with IDP: {}",
-                                                &data.data.user_id
-                                            );
-                                            AppError::NOT_AUTHORIZED
-                                        })?;
-
-                                    Ok(AuthenticatedUser { user })
-                                }
-                            } else {
-                                Err(AppError::NOT_AUTHORIZED.into())
-                            }
-                            */
+                    let claims = match internal_app_data
+                        .sessions
+                        .verify(bearer.token(), &db_pool)
+                        .await
+                    {
+                        Ok(claims) => claims,
+                        Err(_) if is_get => {
+                            return free_user(&internal_app_data, &db_pool, &redis).await
                         }
-                        _ => {
-                            let body = reqwest::get(&url).await;
-                            match body {
-                                Ok(response) => {
-                                    let f_id = response.text().await.unwrap();
-                                    if let Ok(data) = serde_json::from_str::<
-                                        Facebook<FacebookResponseData>,
-                                    >(&f_id)
-                                    {
-                                        // Facebook says the token is valid and it belongs to our specific APP in facebook
-                                        if data.data.is_valid
-                                            && data.data.app_id
-                                                == internal_app_data.facebook_data
-                                                    ["facebook_app_id"]
-                                        {
-                                            let user =
-                                                User::find_by_idp_id(&data.data.user_id, &db_pool)
-                                                    .await?
-                                                    .ok_or_else(|| {
-                                                        debug!(
-                                                            "User not found with IDP: {}",
-                                                            &data.data.user_id
-                                                        );
-                                                        AppError::NOT_AUTHORIZED
-                                                    })?;
-
-                                            // push into REDIS so we don't make request to facebook again to verify
-                                            let key_expire_at_in_seconds =
-                                                data.data.expires_at - current_timestamp;
-
-                                            let user_serialized =
-                                                serde_json::to_string(&user).unwrap();
-
-                                            let _key_set = set_redis_key_with_expiration(
-                                                key,
-                                                user_serialized,
-                                                key_expire_at_in_seconds.to_string(),
-                                                &redis,
-                                            )
-                                            .await?;
-
-                                            // let _one = redis.send(Command(resp_array!["SET", key.as_str(), f_id, "EX", key_expire_at_in_seconds.to_string() ])).await;
-                                            // let _two = redis.send(Command(resp_array!["EXPIREAT", key.as_str(), key_expire_at_in_seconds.to_string() ])).await;
-
-                                            Ok(AuthenticatedUser { user })
-                                        } else {
-                                            // Facebook says the token is INVALID
-                                            Err(AppError::CREDENTIAL_EXPIRED.into())
-                                        }
-                                    } else {
-                                        Err(AppError::NOT_AUTHORIZED.into())
-                                    }
-                                }
-                                Err(e) => {
-                                    debug!("Error while decoding facebook token: {:?}", e);
-                                    Err(AppError::NOT_AUTHORIZED.into())
-                                }
-                            }
-                        }
-                    }
+                        Err(e) => return Err(e),
+                    };
+                    let user =
+                        User::find_by_uuid(claims.sub, &db_pool)
+                            .await?
+                            .ok_or_else(|| {
+                                debug!(
+                                    "User '{}' of session '{}' not found",
+                                    claims.sub, claims.sid
+                                );
+                                AppError::NOT_AUTHORIZED
+                            })?;
+                    Ok(AuthenticatedUser { user })
                 };
 
                 Box::pin(future)
@@ -284,39 +291,8 @@ impl FromRequest for AuthenticatedUser {
 
                 // Allow free users to GET anything as unauthenticated user
                 if method == "GET" {
-                    let future = async move {
-                        let key = format!("free_user");
-                        let free_user_key = get_redis_key::<User>(key.as_str(), &redis).await?;
-
-                        match free_user_key {
-                            Some(user) => Ok(AuthenticatedUser { user }),
-                            _ => {
-                                let user =
-                                    User::find_by_email_id(&internal_app_data.dummy_user, &db_pool)
-                                        .await?
-                                        .ok_or_else(|| {
-                                            debug!(
-                                                "User '{}' not found",
-                                                &internal_app_data.sysadmin
-                                            );
-                                            AppError::NOT_AUTHORIZED
-                                        })?;
-
-                                let key_expire_at_in_seconds: u64 = 86600;
-                                let user_serialized = serde_json::to_string(&user).unwrap();
-
-                                let _key_set = set_redis_key_with_expiration(
-                                    key,
-                                    user_serialized,
-                                    key_expire_at_in_seconds.to_string(),
-                                    &redis,
-                                )
-                                .await?;
-
-                                Ok(AuthenticatedUser { user })
-                            }
-                        }
-                    };
+                    let future =
+                        async move { free_user(&internal_app_data, &db_pool, &redis).await };
                     Box::pin(future)
                 } else {
                     // however if the method is anything other than GET then we need to stop them here
@@ -2,8 +2,8 @@ use actix_web::error::ResponseError;
 use actix_web::http::StatusCode;
 use actix_web::HttpResponse;
 use color_eyre::Report;
-use serde::export::Formatter;
 use serde::{Serialize, Serializer};
+use std::fmt::Formatter;
 use std::{convert::From, error::Error};
 use tracing::error;
 
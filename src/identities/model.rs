@@ -0,0 +1,264 @@
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::web::Data;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Done;
+use sqlx::{FromRow, PgPool, Postgres, Row, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    auth::{ensure_not_revoked, AuthenticatedUser},
+    errors::AppError,
+    idp::VerifiedIdentity,
+    redis::revoke_token,
+    user::{User, UserExternalIDP},
+    InternalAppData,
+};
+
+// this struct will use to receive user input: a token proving the user holds the identity
+#[derive(Serialize, Deserialize)]
+pub struct IdentityProof {
+    // the identity provider, as named in the `idp` header
+    pub idp: String,
+    pub token: String,
+}
+
+// this struct will be used to represent database record
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct UserIdentity {
+    pub provider: UserExternalIDP,
+    pub subject: String,
+    pub linked_at: NaiveDateTime,
+}
+
+// verify the token of the identity being linked or unlinked. The user's own credential was
+// verified by AuthenticatedUser, so together they prove the user holds both identities.
+async fn verify_proof(
+    proof: &IdentityProof,
+    internal_app_data: &InternalAppData,
+    redis: &Data<Addr<RedisActor>>,
+) -> Result<(UserExternalIDP, VerifiedIdentity), AppError> {
+    let provider = internal_app_data
+        .identity_providers
+        .get(&proof.idp)
+        .ok_or_else(|| {
+            AppError::INVALID_INPUT.message(format!("Unknown identity provider {}", proof.idp))
+        })?;
+    ensure_not_revoked(&proof.token, redis).await?;
+    let identity = provider.verify(&proof.token).await?;
+    Ok((provider.idp(), identity))
+}
+
+impl UserIdentity {
+    pub async fn find_all(
+        user: AuthenticatedUser,
+        pool: &PgPool,
+    ) -> Result<Vec<UserIdentity>, AppError> {
+        let identities = sqlx::query_as::<_, UserIdentity>(
+            "select
+                    provider,
+                    subject,
+                    linked_at
+                from
+                    user_identities
+                where
+                    user_id = $1
+                order by
+                    linked_at",
+        )
+        .bind(user.user.id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(identities)
+    }
+
+    // add an identity to the user, so either identity signs in to the same account. Signing in with
+    // the identity before linking it created a user of its own; while that user has not completed
+    // onboarding it is merged into this one.
+    pub async fn link(
+        user: AuthenticatedUser,
+        proof: IdentityProof,
+        internal_app_data: &InternalAppData,
+        pool: &PgPool,
+        redis: &Data<Addr<RedisActor>>,
+    ) -> Result<UserIdentity, AppError> {
+        let (provider, identity) = verify_proof(&proof, internal_app_data, redis).await?;
+
+        let mut tx = pool.begin().await?;
+        // a first login with the identity must not create a user of its own meanwhile
+        User::lock_identity(&provider, &identity.subject, &mut tx).await?;
+        let linked = sqlx::query(
+            "INSERT INTO USER_IDENTITIES (PROVIDER, SUBJECT, USER_ID) VALUES ($1, $2, $3)
+                ON CONFLICT (PROVIDER, SUBJECT) DO NOTHING
+                RETURNING PROVIDER, SUBJECT, LINKED_AT",
+        )
+        .bind(&provider)
+        .bind(&identity.subject)
+        .bind(user.user.id)
+        .map(|row: PgRow| UserIdentity {
+            provider: row.get(0),
+            subject: row.get(1),
+            linked_at: row.get(2),
+        })
+        .fetch_optional(&mut tx)
+        .await?;
+        if let Some(linked) = linked {
+            tx.commit().await?;
+            return Ok(linked);
+        }
+
+        // already linked: fine if it is this user's
+        let (existing, owner_id) = sqlx::query(
+            "SELECT PROVIDER, SUBJECT, LINKED_AT, USER_ID FROM USER_IDENTITIES
+                where PROVIDER = $1 and SUBJECT = $2",
+        )
+        .bind(&provider)
+        .bind(&identity.subject)
+        .map(|row: PgRow| {
+            let identity = UserIdentity {
+                provider: row.get(0),
+                subject: row.get(1),
+                linked_at: row.get(2),
+            };
+            (identity, row.get::<Uuid, _>(3))
+        })
+        .fetch_one(&mut tx)
+        .await?;
+        if owner_id == user.user.id {
+            tx.commit().await?;
+            return Ok(existing);
+        }
+
+        if !UserIdentity::merge_user(owner_id, user.user.id, &mut tx).await? {
+            debug!(
+                "{} identity {} is linked to onboarded user {}, not merged into {}",
+                provider, identity.subject, owner_id, user.user.id
+            );
+            return Err(AppError::INVALID_INPUT
+                .message("This identity is linked to another account".to_string()));
+        }
+        tx.commit().await?;
+        debug!("User {} merged into {}", owner_id, user.user.id);
+        Ok(existing)
+    }
+
+    // move everything of a user who has not completed onboarding to another user, and delete them.
+    // Returns false, merging nothing, once the user has completed onboarding.
+    async fn merge_user(
+        from_user_id: Uuid,
+        into_user_id: Uuid,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, AppError> {
+        // locked in a fixed order, so concurrent merges and unlinks of the two cannot deadlock
+        let from_user = sqlx::query(
+            "SELECT ID, SIGN_ID, EMAIL FROM USERS where ID in ($1, $2) ORDER BY ID FOR UPDATE",
+        )
+        .bind(from_user_id)
+        .bind(into_user_id)
+        .map(|row: PgRow| {
+            (
+                row.get::<Uuid, _>(0),
+                row.get::<Option<i32>, _>(1),
+                row.get::<Option<String>, _>(2),
+            )
+        })
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .find(|(id, _, _)| *id == from_user_id);
+        let email = match from_user {
+            Some((_, None, email)) => email,
+            _ => return Ok(false),
+        };
+
+        for statement in [
+            "UPDATE USER_IDENTITIES set USER_ID = $2 where USER_ID = $1",
+            "UPDATE SESSIONS set USER_ID = $2 where USER_ID = $1",
+            "UPDATE QUALITY set CREATED_BY = $2 where CREATED_BY = $1",
+            "UPDATE QUALITY set UPDATED_BY = $2 where UPDATED_BY = $1",
+            "UPDATE CHOICE set CREATED_BY = $2 where CREATED_BY = $1",
+            "UPDATE CHOICE set UPDATED_BY = $2 where UPDATED_BY = $1",
+            "UPDATE VOTES set USER_ID = $2 where USER_ID = $1",
+        ]
+        .iter()
+        {
+            sqlx::query(statement)
+                .bind(from_user_id)
+                .bind(into_user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM USERS where ID = $1")
+            .bind(from_user_id)
+            .execute(&mut *tx)
+            .await?;
+        // the email is kept if this user has none, now that the merged user no longer holds it
+        sqlx::query(
+            "UPDATE USERS set EMAIL = coalesce(EMAIL, $2), UPDATED_AT = current_timestamp
+                where ID = $1",
+        )
+        .bind(into_user_id)
+        .bind(email)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(true)
+    }
+
+    // remove an identity from the user. The last one is kept, or the user could no longer sign in.
+    pub async fn unlink(
+        user: AuthenticatedUser,
+        proof: IdentityProof,
+        internal_app_data: &InternalAppData,
+        pool: &PgPool,
+        redis: &Data<Addr<RedisActor>>,
+    ) -> Result<(), AppError> {
+        let (provider, identity) = verify_proof(&proof, internal_app_data, redis).await?;
+
+        let mut tx = pool.begin().await?;
+        // concurrent unlinks of the user's identities must not remove the last one between them
+        sqlx::query("SELECT ID FROM USERS where ID = $1 FOR UPDATE")
+            .bind(user.user.id)
+            .execute(&mut tx)
+            .await?;
+        let unlinked = sqlx::query(
+            "DELETE FROM USER_IDENTITIES where USER_ID = $1 and PROVIDER = $2 and SUBJECT = $3
+                and (SELECT count(*) FROM USER_IDENTITIES where USER_ID = $1) > 1",
+        )
+        .bind(user.user.id)
+        .bind(&provider)
+        .bind(&identity.subject)
+        .execute(&mut tx)
+        .await?;
+        if unlinked.rows_affected() == 0 {
+            return Err(AppError::INVALID_INPUT.message(
+                "This identity is not linked to this account, or is its only one".to_string(),
+            ));
+        }
+
+        // sessions opened with the identity end with it. Cached bearer tokens of the identity
+        // need nothing: their user is looked up through the identity, which is now gone.
+        sqlx::query(
+            "UPDATE SESSIONS set REVOKED_AT = current_timestamp, UPDATED_AT = current_timestamp
+                where USER_ID = $1 and PROVIDER = $2 and SUBJECT = $3 and REVOKED_AT is null",
+        )
+        .bind(user.user.id)
+        .bind(&provider)
+        .bind(&identity.subject)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        // the proof token was handed in to give the identity up, so it is not accepted again
+        revoke_token(
+            &google_jwt_verify::revocation_id(&proof.token),
+            identity.expires_at,
+            redis,
+        )
+        .await?;
+        Ok(())
+    }
+}
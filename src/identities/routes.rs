@@ -0,0 +1,89 @@
+use crate::{
+    auth::AuthenticatedUser,
+    identities::{IdentityProof, UserIdentity},
+    InternalAppData,
+};
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::{delete, get, post, web, HttpResponse, Responder, ResponseError};
+use sqlx::PgPool;
+
+#[get("/user/identities")]
+async fn find_all(user: AuthenticatedUser, db_pool: web::Data<PgPool>) -> impl Responder {
+    let result = UserIdentity::find_all(user, db_pool.get_ref()).await;
+    match result {
+        Ok(identities) => HttpResponse::Ok().json(identities),
+        Err(e) => {
+            debug!(
+                "Error occurred in Identities >> routes.rs find_all function: \n{}",
+                e
+            );
+            e.error_response()
+        }
+    }
+}
+
+// link the identity proven by the body's token to the signed in user
+#[post("/user/identities")]
+async fn link(
+    user: AuthenticatedUser,
+    proof: web::Json<IdentityProof>,
+    internal_app_data: web::Data<InternalAppData>,
+    db_pool: web::Data<PgPool>,
+    redis: web::Data<Addr<RedisActor>>,
+) -> impl Responder {
+    let result = UserIdentity::link(
+        user,
+        proof.into_inner(),
+        internal_app_data.get_ref(),
+        db_pool.get_ref(),
+        &redis,
+    )
+    .await;
+
+    match result {
+        Ok(identity) => HttpResponse::Ok().json(identity),
+        Err(e) => {
+            debug!(
+                "Error occurred in Identities >> routes.rs link function: \n{}",
+                e
+            );
+            e.error_response()
+        }
+    }
+}
+
+#[delete("/user/identities")]
+async fn unlink(
+    user: AuthenticatedUser,
+    proof: web::Json<IdentityProof>,
+    internal_app_data: web::Data<InternalAppData>,
+    db_pool: web::Data<PgPool>,
+    redis: web::Data<Addr<RedisActor>>,
+) -> impl Responder {
+    let result = UserIdentity::unlink(
+        user,
+        proof.into_inner(),
+        internal_app_data.get_ref(),
+        db_pool.get_ref(),
+        &redis,
+    )
+    .await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            debug!(
+                "Error occurred in Identities >> routes.rs unlink function: \n{}",
+                e
+            );
+            e.error_response()
+        }
+    }
+}
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(find_all);
+    cfg.service(link);
+    cfg.service(unlink);
+}
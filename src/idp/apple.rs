@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Deserializer};
+
+use super::{id_token_error, IdentityProvider, Profile, VerifiedIdentity};
+use crate::{errors::AppError, user::UserExternalIDP, GoogleAsyncClient};
+
+pub const APPLE_ISSUER: &str = "https://appleid.apple.com";
+pub const APPLE_JWKS_URL: &str = "https://appleid.apple.com/auth/keys";
+
+/// Addresses created by "Hide My Email", which forward to the user's real address.
+const PRIVATE_RELAY_DOMAIN: &str = "@privaterelay.appleid.com";
+
+#[derive(Deserialize)]
+struct ApplePayload {
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    email_verified: bool,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    is_private_email: bool,
+}
+
+// Apple sends boolean claims either as booleans or as the strings "true" and "false"
+fn deserialize_flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        Text(String),
+    }
+    match Flag::deserialize(deserializer)? {
+        Flag::Bool(value) => Ok(value),
+        Flag::Text(text) => Ok(text == "true"),
+    }
+}
+
+/// Sign in with Apple identity tokens issued to the app's bundle or services IDs.
+///
+/// Apple never puts the user's name in the identity token, and users may hide their
+/// address behind a private relay, so the profile often only has a relay email.
+pub struct AppleProvider {
+    client: GoogleAsyncClient,
+}
+
+impl AppleProvider {
+    pub fn new(client: GoogleAsyncClient) -> Self {
+        AppleProvider { client }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for AppleProvider {
+    fn idp(&self) -> UserExternalIDP {
+        UserExternalIDP::Apple
+    }
+
+    async fn verify(&self, token: &str) -> Result<VerifiedIdentity, AppError> {
+        let token = self
+            .client
+            .verify_token_with_payload_async::<ApplePayload>(token)
+            .await
+            .map_err(|e| id_token_error(UserExternalIDP::Apple, e))?;
+        let payload = token.get_payload();
+        let is_private_relay = payload.is_private_email
+            || payload
+                .email
+                .as_ref()
+                .is_some_and(|email| email.to_lowercase().ends_with(PRIVATE_RELAY_DOMAIN));
+        Ok(VerifiedIdentity {
+            subject: token.get_claims().get_subject(),
+            expires_at: token.get_claims().get_expires_at(),
+            profile: Profile {
+                email: payload.email.clone(),
+                email_verified: payload.email_verified,
+                email_is_private_relay: is_private_relay,
+                ..Profile::default()
+            },
+        })
+    }
+}
@@ -0,0 +1,81 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{IdentityProvider, Profile, VerifiedIdentity};
+use crate::{errors::AppError, user::UserExternalIDP};
+
+#[derive(Serialize, Deserialize)]
+pub struct Facebook<T> {
+    pub data: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FacebookResponseData {
+    pub app_id: String,
+    pub r#type: String,
+    pub application: String,
+    pub data_access_expires_at: u64,
+    pub error: Option<FacebookError>,
+    pub expires_at: u64,
+    pub is_valid: bool,
+    pub scopes: Vec<String>,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FacebookError {
+    pub code: u32,
+    pub message: String,
+    pub subcode: u32,
+}
+
+/// Facebook Login user access tokens, checked through the Graph API's `debug_token`.
+pub struct FacebookProvider {
+    app_id: String,
+    access_token: String,
+}
+
+impl FacebookProvider {
+    pub fn new(app_id: &str, access_token: &str) -> Self {
+        FacebookProvider {
+            app_id: app_id.to_string(),
+            access_token: access_token.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for FacebookProvider {
+    fn idp(&self) -> UserExternalIDP {
+        UserExternalIDP::Facebook
+    }
+
+    async fn verify(&self, token: &str) -> Result<VerifiedIdentity, AppError> {
+        let url = format!(
+            "https://graph.facebook.com/debug_token?input_token={}&access_token={}",
+            token, self.access_token
+        );
+        let body = match reqwest::get(&url).await {
+            Ok(response) => response.text().await.unwrap_or_default(),
+            Err(e) => {
+                debug!("Error while decoding facebook token: {:?}", e);
+                return Err(AppError::NOT_AUTHORIZED.into());
+            }
+        };
+        let data = match serde_json::from_str::<Facebook<FacebookResponseData>>(&body) {
+            Ok(response) => response.data,
+            Err(_) => return Err(AppError::NOT_AUTHORIZED.into()),
+        };
+
+        // Facebook says the token is valid and it belongs to our specific APP in facebook
+        if !data.is_valid || data.app_id != self.app_id {
+            return Err(AppError::CREDENTIAL_EXPIRED.into());
+        }
+
+        Ok(VerifiedIdentity {
+            subject: data.user_id,
+            expires_at: data.expires_at,
+            profile: Profile::default(),
+        })
+    }
+}
@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+
+use super::{id_token_error, IdentityProvider, Profile, VerifiedIdentity};
+use crate::{errors::AppError, user::UserExternalIDP, GoogleAsyncClient};
+
+/// Firebase Authentication ID tokens of the configured Firebase project.
+pub struct FirebaseProvider {
+    client: GoogleAsyncClient,
+}
+
+impl FirebaseProvider {
+    pub fn new(client: GoogleAsyncClient) -> Self {
+        FirebaseProvider { client }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for FirebaseProvider {
+    fn idp(&self) -> UserExternalIDP {
+        UserExternalIDP::Firebase
+    }
+
+    async fn verify(&self, token: &str) -> Result<VerifiedIdentity, AppError> {
+        let token = self
+            .client
+            .verify_firebase_token_async(token)
+            .await
+            .map_err(|e| id_token_error(UserExternalIDP::Firebase, e))?;
+        let payload = token.get_payload();
+        Ok(VerifiedIdentity {
+            subject: payload.get_user_id(),
+            expires_at: token.get_claims().get_expires_at(),
+            profile: Profile {
+                email: payload.get_email(),
+                email_verified: payload.is_email_verified(),
+                name: payload.get_name(),
+                ..Profile::default()
+            },
+        })
+    }
+}
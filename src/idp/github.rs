@@ -0,0 +1,122 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use serde::Deserialize;
+
+use super::{IdentityProvider, Profile, VerifiedIdentity};
+use crate::{errors::AppError, user::UserExternalIDP};
+
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// How long a checked token is trusted. OAuth app tokens do not expire, but users can
+/// revoke them on GitHub at any time.
+const CHECKED_TOKEN_TTL: u64 = 300;
+
+#[derive(Deserialize)]
+struct GitHubToken {
+    #[serde(default)]
+    expires_at: Option<String>,
+    user: GitHubUser,
+}
+
+#[derive(Deserialize)]
+struct GitHubUser {
+    id: u64,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+}
+
+/// GitHub OAuth access tokens, checked with the OAuth app's credentials so that tokens
+/// issued to other apps are rejected.
+pub struct GitHubProvider {
+    api_url: String,
+    client_id: String,
+    client_secret: String,
+}
+
+impl GitHubProvider {
+    /// `api_url` is GitHub's REST API, or a GitHub Enterprise or stub server in its place.
+    pub fn new(api_url: &str, client_id: &str, client_secret: &str) -> Self {
+        GitHubProvider {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for GitHubProvider {
+    fn idp(&self) -> UserExternalIDP {
+        UserExternalIDP::GitHub
+    }
+
+    async fn verify(&self, token: &str) -> Result<VerifiedIdentity, AppError> {
+        let url = format!("{}/applications/{}/token", self.api_url, self.client_id);
+        let body = serde_json::json!({ "access_token": token }).to_string();
+        let response = reqwest::Client::new()
+            .post(&url)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .header("Accept", "application/vnd.github+json")
+            .header("Content-Type", "application/json")
+            // GitHub rejects requests without a User-Agent
+            .header("User-Agent", "astrolytic")
+            .body(body)
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Could not check GitHub token: {:?}", e);
+                return Err(AppError::INTERNAL_ERROR.default());
+            }
+        };
+        // GitHub answers 404 for tokens that are invalid or were issued to another app
+        if response.status().is_client_error() {
+            debug!("GitHub token rejected: {}", response.status());
+            return Err(AppError::NOT_AUTHORIZED.into());
+        }
+        if !response.status().is_success() {
+            warn!("Could not check GitHub token: {}", response.status());
+            return Err(AppError::INTERNAL_ERROR.default());
+        }
+        let text = response.text().await.unwrap_or_default();
+        let checked = match serde_json::from_str::<GitHubToken>(&text) {
+            Ok(checked) => checked,
+            Err(e) => {
+                warn!("Unexpected GitHub token check response: {}", e);
+                return Err(AppError::INTERNAL_ERROR.default());
+            }
+        };
+
+        let current_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let trusted_until = current_timestamp + CHECKED_TOKEN_TTL;
+        let expires_at = match checked.expires_at {
+            Some(ref expires_at) => match DateTime::parse_from_rfc3339(expires_at) {
+                Ok(expires_at) => (expires_at.timestamp().max(0) as u64).min(trusted_until),
+                Err(_) => current_timestamp,
+            },
+            None => trusted_until,
+        };
+        if expires_at <= current_timestamp {
+            return Err(AppError::CREDENTIAL_EXPIRED.into());
+        }
+
+        Ok(VerifiedIdentity {
+            subject: checked.user.id.to_string(),
+            expires_at,
+            profile: Profile {
+                // the public profile email is set by the user and not necessarily verified
+                email: checked.user.email,
+                name: checked.user.name,
+                ..Profile::default()
+            },
+        })
+    }
+}
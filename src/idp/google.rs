@@ -0,0 +1,85 @@
+use async_trait::async_trait;
+use google_jwt_verify::{Error, TokenInfoClient};
+
+use super::{id_token_error, IdentityProvider, Profile, VerifiedIdentity};
+use crate::{errors::AppError, user::UserExternalIDP, GoogleAsyncClient};
+
+/// Google Sign-In ID tokens, and OAuth access tokens of clients that only hold one.
+pub struct GoogleProvider {
+    client: GoogleAsyncClient,
+    tokeninfo: TokenInfoClient,
+    // Google Workspace domains whose users are flagged as internal staff
+    internal_domains: Vec<String>,
+}
+
+impl GoogleProvider {
+    pub fn new(
+        client: GoogleAsyncClient,
+        tokeninfo: TokenInfoClient,
+        internal_domains: Vec<String>,
+    ) -> Self {
+        GoogleProvider {
+            client,
+            tokeninfo,
+            internal_domains,
+        }
+    }
+
+    async fn verify_access_token(&self, token: &str) -> Result<VerifiedIdentity, AppError> {
+        let info = self
+            .tokeninfo
+            .verify_access_token_async(token)
+            .await
+            .map_err(|e| id_token_error(UserExternalIDP::Google, e))?;
+        let subject = match info.get_subject() {
+            Some(subject) => subject,
+            None => {
+                debug!("Google access token was not granted an identity scope");
+                return Err(AppError::NOT_AUTHORIZED.into());
+            }
+        };
+        Ok(VerifiedIdentity {
+            subject,
+            expires_at: info.get_expires_at(),
+            profile: Profile {
+                email: info.get_email(),
+                email_verified: info.is_email_verified(),
+                ..Profile::default()
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for GoogleProvider {
+    fn idp(&self) -> UserExternalIDP {
+        UserExternalIDP::Google
+    }
+
+    async fn verify(&self, token: &str) -> Result<VerifiedIdentity, AppError> {
+        let id_token = match self.client.verify_id_token_async(token).await {
+            Ok(id_token) => id_token,
+            // the bearer is not a JWT, so it may be an OAuth access token
+            Err(Error::MalformedToken)
+            | Err(Error::MalformedSegment(..))
+            | Err(Error::InvalidHeader(_)) => return self.verify_access_token(token).await,
+            Err(e) => return Err(id_token_error(UserExternalIDP::Google, e)),
+        };
+        let payload = id_token.get_payload();
+        // staff signing in through the company's Google Workspace domain
+        let is_internal = payload
+            .get_domain()
+            .is_some_and(|hd| self.internal_domains.contains(&hd));
+        Ok(VerifiedIdentity {
+            subject: id_token.get_claims().get_subject(),
+            expires_at: id_token.get_claims().get_expires_at(),
+            profile: Profile {
+                email: Some(payload.get_email()),
+                email_verified: payload.is_email_verified(),
+                name: payload.get_name(),
+                is_internal,
+                ..Profile::default()
+            },
+        })
+    }
+}
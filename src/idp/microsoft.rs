@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{id_token_error, IdentityProvider, Profile, VerifiedIdentity};
+use crate::{errors::AppError, user::UserExternalIDP, GoogleAsyncClient};
+
+/// Signing keys of the Microsoft identity platform, shared by all Entra ID tenants.
+pub const MICROSOFT_JWKS_URL: &str = "https://login.microsoftonline.com/common/discovery/v2.0/keys";
+
+/// The `iss` of v2.0 ID tokens issued by an Entra ID tenant.
+pub fn microsoft_issuer(tenant_id: &str) -> String {
+    format!("https://login.microsoftonline.com/{}/v2.0", tenant_id)
+}
+
+#[derive(Deserialize)]
+struct MicrosoftPayload {
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Microsoft work and school accounts, through ID tokens of the configured Entra ID tenants.
+pub struct MicrosoftProvider {
+    client: GoogleAsyncClient,
+}
+
+impl MicrosoftProvider {
+    pub fn new(client: GoogleAsyncClient) -> Self {
+        MicrosoftProvider { client }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for MicrosoftProvider {
+    fn idp(&self) -> UserExternalIDP {
+        UserExternalIDP::Microsoft
+    }
+
+    async fn verify(&self, token: &str) -> Result<VerifiedIdentity, AppError> {
+        let token = self
+            .client
+            .verify_token_with_payload_async::<MicrosoftPayload>(token)
+            .await
+            .map_err(|e| id_token_error(UserExternalIDP::Microsoft, e))?;
+        let payload = token.get_payload();
+        Ok(VerifiedIdentity {
+            subject: token.get_claims().get_subject(),
+            expires_at: token.get_claims().get_expires_at(),
+            profile: Profile {
+                // tenant admins can set any address, so Microsoft's email is never verified
+                email: payload.email.clone(),
+                name: payload.name.clone(),
+                ..Profile::default()
+            },
+        })
+    }
+}
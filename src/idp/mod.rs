@@ -0,0 +1,93 @@
+mod apple;
+mod facebook;
+mod firebase;
+mod github;
+mod google;
+mod microsoft;
+
+pub use apple::{AppleProvider, APPLE_ISSUER, APPLE_JWKS_URL};
+pub use facebook::FacebookProvider;
+pub use firebase::FirebaseProvider;
+pub use github::{GitHubProvider, GITHUB_API_URL};
+pub use google::GoogleProvider;
+pub use microsoft::{microsoft_issuer, MicrosoftProvider, MICROSOFT_JWKS_URL};
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{errors::AppError, user::UserExternalIDP};
+
+/// What an identity provider vouches for about the bearer of a token.
+pub struct VerifiedIdentity {
+    /// The user's ID at the provider, stored as the user's `external_idp_id`.
+    pub subject: String,
+    /// When the token stops being valid; the authenticated user is cached until then.
+    pub expires_at: u64,
+    pub profile: Profile,
+}
+
+#[derive(Default)]
+pub struct Profile {
+    pub email: Option<String>,
+    pub email_verified: bool,
+    /// The email is a relay address that forwards to the user, such as Apple's "Hide My
+    /// Email", so it says nothing about other accounts with the user's real address. Relay
+    /// addresses are not stored as the account email.
+    pub email_is_private_relay: bool,
+    pub name: Option<String>,
+    /// The provider vouches that the user is staff, e.g. a Google Workspace login.
+    pub is_internal: bool,
+}
+
+/// Verifies the bearer tokens of one external identity provider.
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// The provider users signing in through it are stored under.
+    fn idp(&self) -> UserExternalIDP;
+
+    async fn verify(&self, token: &str) -> Result<VerifiedIdentity, AppError>;
+}
+
+/// The identity providers accepted by `AuthenticatedUser`, keyed by the value of the `idp`
+/// request header.
+#[derive(Clone, Default)]
+pub struct IdentityProviders {
+    providers: HashMap<String, Arc<dyn IdentityProvider>>,
+}
+
+impl IdentityProviders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<P: IdentityProvider + 'static>(&mut self, name: &str, provider: P) {
+        self.providers.insert(name.to_string(), Arc::new(provider));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn IdentityProvider>> {
+        self.providers.get(name).cloned()
+    }
+}
+
+/// Maps an ID token verification failure to the 401 reason returned to the client.
+pub fn id_token_error(issuer: impl fmt::Display, e: google_jwt_verify::Error) -> AppError {
+    use google_jwt_verify::Error;
+    match e {
+        Error::Expired | Error::NotYetValid => {
+            debug!("{} token rejected: {}", issuer, e);
+            AppError::CREDENTIAL_EXPIRED.message(e.to_string())
+        }
+        Error::RetrieveKeyFailure | Error::RevocationCheckFailure | Error::Crypto(_) => {
+            warn!("Could not verify {} token: {}", issuer, e);
+            AppError::INTERNAL_ERROR.default()
+        }
+        _ => {
+            match std::error::Error::source(&e) {
+                Some(source) => debug!("{} token rejected: {} ({})", issuer, e, source),
+                None => debug!("{} token rejected: {}", issuer, e),
+            }
+            AppError::NOT_AUTHORIZED.message(e.to_string())
+        }
+    }
+}
@@ -15,16 +15,39 @@ use anyhow::Result;
 use dotenv::dotenv;
 use listenfd::ListenFd;
 use sqlx::PgPool;
-use std::{collections::HashMap, env};
+use std::{env, fs, time::Duration};
 
-use google_jwt_verify::AsyncClient as GoogleAsyncClient;
+use google_jwt_verify::{
+    Algorithm, AsyncClient, AsyncKeyProvider, GenericAsyncClient, OfflineKeyProvider,
+    TokenInfoClient, TokenSigner, FIREBASE_JWKS_URL, GOOGLE_CERT_URL,
+};
+
+use crate::idp::{
+    microsoft_issuer, AppleProvider, FacebookProvider, FirebaseProvider, GitHubProvider,
+    GoogleProvider, IdentityProviders, MicrosoftProvider, APPLE_ISSUER, APPLE_JWKS_URL,
+    GITHUB_API_URL, MICROSOFT_JWKS_URL,
+};
+use crate::redis::{
+    purge_legacy_token_cache, RedisKeyProvider, RedisRevocationList, TokenCacheKeys,
+};
+use crate::session::SessionIssuer;
+
+/// Google and Firebase ID token verifier. The key provider is boxed so that tests can swap Google's
+/// certificate endpoint for `google_jwt_verify::testing::InMemoryKeyProvider`.
+pub type GoogleAsyncClient = GenericAsyncClient<Box<dyn AsyncKeyProvider>>;
 
 // import todo module (routes and model)
+mod admin;
 mod auth;
 mod errors;
+mod identities;
+mod idp;
 mod quality;
 mod redis;
+mod session;
 mod signs;
+#[cfg(test)]
+mod tests;
 mod todo;
 mod user;
 mod votes;
@@ -43,6 +66,20 @@ async fn index() -> impl Responder {
     )
 }
 
+// the routes of every module, shared with the tests
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(index));
+    admin::init(cfg);
+    // registered before user::init, whose /user/{id} would otherwise match /user/identities
+    identities::init(cfg);
+    quality::init(cfg);
+    session::init(cfg);
+    signs::init(cfg);
+    todo::init(cfg);
+    user::init(cfg);
+    votes::init(cfg);
+}
+
 // https://auth0.com/blog/build-an-api-in-rust-with-jwt-authentication-using-actix-web/
 /*async fn validator(&mut req: ServiceRequest, credentials: BearerAuth) -> Result<ServiceRequest, Error> {
     let mut client = google_signin::Client::new();
@@ -91,8 +128,9 @@ async fn index() -> impl Responder {
 
 #[derive(Clone)]
 pub struct InternalAppData {
-    google_client: GoogleAsyncClient,
-    facebook_data: HashMap<String, String>,
+    identity_providers: IdentityProviders,
+    sessions: SessionIssuer,
+    token_cache_keys: TokenCacheKeys,
     sysadmin: String,
     dummy_user: String,
 }
@@ -118,40 +156,195 @@ async fn main() -> Result<()> {
 
     let google_client_id =
         env::var("GOOGLE_CLIENT_ID").expect("GOOGLE_CLIENT_ID is not set in .env file");
+    // optional: Google Workspace domains whose users are flagged as internal staff
+    let google_internal_domains: Vec<String> = env::var("GOOGLE_INTERNAL_DOMAINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|domain| !domain.is_empty())
+        .map(String::from)
+        .collect();
     let sysadmin = env::var("SYSADMIN").expect("SYSADMIN is not set in .env file");
     let dummy_user = env::var("DUMMY_USER").expect("DUMMY_USER is not set in .env file");
     let facebook_app_id =
         env::var("FACEBOOK_APP_ID").expect("FACEBOOK_APP_ID is not set in .env file");
-    let facebook_secret =
-        env::var("FACEBOOK_SECRET").expect("FACEBOOK_SECRET is not set in .env file");
     let facebook_access_token =
         env::var("FACEBOOK_ACCESS_TOKEN").expect("FACEBOOK_ACCESS_TOKEN is not set in .env file");
 
-    let mut data = HashMap::new();
+    // REDIS
+    let redis_host = env::var("REDIS_HOST").expect("HOST is not set in .env file");
+    let redis_port = env::var("REDIS_PORT").expect("PORT is not set in .env file");
 
-    data.insert("facebook_app_id".to_string(), facebook_app_id);
-    data.insert("facebook_secret".to_string(), facebook_secret);
-    data.insert("facebook_access_token".to_string(), facebook_access_token);
+    let redis_addr = RedisActor::start(format!("{}:{}", redis_host, redis_port));
 
-    let g_client = google_jwt_verify::AsyncClient::new(&google_client_id);
+    // GOOGLE_CLIENT_ID may hold a comma separated list (web, iOS, Android client IDs)
+    let google_client_ids: Vec<&str> = google_client_id
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .collect();
+    // optional: GOOGLE_JWKS holds Google's key set as inline JSON, a file or a directory,
+    // for environments without outbound internet. Files are reloaded when they change.
+    let google_keys: Box<dyn AsyncKeyProvider> = match env::var("GOOGLE_JWKS") {
+        Ok(jwks) if jwks.trim_start().starts_with('{') => {
+            Box::new(OfflineKeyProvider::from_json(&jwks)?)
+        }
+        Ok(path) => Box::new(OfflineKeyProvider::from_path(&path)?),
+        // share Google's certificates between all instances instead of each downloading them
+        Err(_) => Box::new(RedisKeyProvider::new(
+            GOOGLE_CERT_URL,
+            "jwks-google",
+            web::Data::new(redis_addr.clone()),
+        )),
+    };
+    let g_client = AsyncClient::builder_with_audiences(&google_client_ids)
+        .custom_key_provider(google_keys)
+        // Google only signs ID tokens with RS256
+        .algorithms(&[Algorithm::RS256])
+        // tolerate a minute of clock drift between Google and this server
+        .leeway(Duration::from_secs(60))
+        .build();
+
+    // clients holding only an OAuth access token are checked through Google's tokeninfo endpoint
+    let google_tokeninfo = TokenInfoClient::builder_with_audiences(&google_client_ids).build();
+
+    // optional: Firebase Authentication ID tokens are accepted once the project is configured
+    let firebase_client = env::var("FIREBASE_PROJECT_ID").ok().map(|project_id| {
+        let firebase_keys: Box<dyn AsyncKeyProvider> = Box::new(RedisKeyProvider::new(
+            FIREBASE_JWKS_URL,
+            "jwks-firebase",
+            web::Data::new(redis_addr.clone()),
+        ));
+        AsyncClient::firebase_builder(&project_id)
+            .custom_key_provider(firebase_keys)
+            .leeway(Duration::from_secs(60))
+            .build()
+    });
+
+    // optional: Sign in with Apple is accepted once the app's bundle or services IDs are
+    // configured, as a comma separated list
+    let apple_client = env::var("APPLE_CLIENT_ID").ok().map(|apple_client_id| {
+        let apple_client_ids: Vec<&str> = apple_client_id
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .collect();
+        let apple_keys: Box<dyn AsyncKeyProvider> = Box::new(RedisKeyProvider::new(
+            APPLE_JWKS_URL,
+            "jwks-apple",
+            web::Data::new(redis_addr.clone()),
+        ));
+        AsyncClient::builder_with_audiences(&apple_client_ids)
+            .custom_key_provider(apple_keys)
+            .issuers(&[APPLE_ISSUER])
+            .algorithms(&[Algorithm::RS256])
+            .leeway(Duration::from_secs(60))
+            .build()
+    });
+
+    // optional: Microsoft work and school accounts of the listed Entra ID tenants
+    let microsoft_client = match (
+        env::var("MICROSOFT_CLIENT_ID"),
+        env::var("MICROSOFT_TENANT_IDS"),
+    ) {
+        (Ok(microsoft_client_id), Ok(tenant_ids)) => {
+            let microsoft_issuers: Vec<String> = tenant_ids
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(microsoft_issuer)
+                .collect();
+            let microsoft_keys: Box<dyn AsyncKeyProvider> = Box::new(RedisKeyProvider::new(
+                MICROSOFT_JWKS_URL,
+                "jwks-microsoft",
+                web::Data::new(redis_addr.clone()),
+            ));
+            Some(
+                AsyncClient::builder(&microsoft_client_id)
+                    .custom_key_provider(microsoft_keys)
+                    .issuers(&microsoft_issuers)
+                    .algorithms(&[Algorithm::RS256])
+                    .leeway(Duration::from_secs(60))
+                    .build(),
+            )
+        }
+        _ => None,
+    };
+
+    // optional: GitHub OAuth app tokens. GITHUB_API_URL points at GitHub Enterprise or a stub
+    let github_provider = match (
+        env::var("GITHUB_CLIENT_ID"),
+        env::var("GITHUB_CLIENT_SECRET"),
+    ) {
+        (Ok(github_client_id), Ok(github_client_secret)) => Some(GitHubProvider::new(
+            &env::var("GITHUB_API_URL").unwrap_or_else(|_| GITHUB_API_URL.to_string()),
+            &github_client_id,
+            &github_client_secret,
+        )),
+        _ => None,
+    };
+
+    // the `idp` request header selects the provider a bearer token is verified with
+    let mut identity_providers = IdentityProviders::new();
+    identity_providers.register(
+        "Google",
+        GoogleProvider::new(g_client, google_tokeninfo, google_internal_domains),
+    );
+    identity_providers.register(
+        "Facebook",
+        FacebookProvider::new(&facebook_app_id, &facebook_access_token),
+    );
+    if let Some(firebase_client) = firebase_client {
+        identity_providers.register("Firebase", FirebaseProvider::new(firebase_client));
+    }
+    if let Some(apple_client) = apple_client {
+        identity_providers.register("Apple", AppleProvider::new(apple_client));
+    }
+    if let Some(microsoft_client) = microsoft_client {
+        identity_providers.register("Microsoft", MicrosoftProvider::new(microsoft_client));
+    }
+    if let Some(github_provider) = github_provider {
+        identity_providers.register("GitHub", github_provider);
+    }
+
+    // optional: SESSION_SIGNING_KEY is the PEM private key our own access tokens are signed with.
+    // Without it every instance signs with a key of its own, lost on restart.
+    let session_signer = match env::var("SESSION_SIGNING_KEY") {
+        Ok(path) => TokenSigner::from_pem(&fs::read(&path)?, "session")?,
+        Err(_) => {
+            warn!("SESSION_SIGNING_KEY is not set, sessions will not survive a restart or be shared between instances");
+            TokenSigner::generate("session")?
+        }
+    };
+    let sessions = SessionIssuer::new(
+        session_signer,
+        RedisRevocationList::new(web::Data::new(redis_addr.clone())),
+    );
+
+    // TOKEN_CACHE_SECRET keys the hashes bearer tokens are cached in Redis under. It is shared by
+    // all instances, so they share cached tokens, and kept secret, so the hashes reveal nothing.
+    let token_cache_secret =
+        env::var("TOKEN_CACHE_SECRET").expect("TOKEN_CACHE_SECRET is not set in .env file");
+    let token_cache_keys = TokenCacheKeys::new(token_cache_secret.as_bytes())?;
+    // tokens used to be cached under the raw token, leaving live credentials in Redis
+    match purge_legacy_token_cache(&web::Data::new(redis_addr.clone())).await {
+        Ok(Some(deleted)) => info!("Deleted {} legacy token cache entries", deleted),
+        Ok(None) => (),
+        Err(e) => warn!("Could not delete legacy token cache entries: {}", e),
+    }
 
     let internal_app_data = InternalAppData {
-        google_client: g_client,
+        identity_providers,
+        sessions,
+        token_cache_keys,
         sysadmin,
         dummy_user,
-        facebook_data: data,
     };
 
     // POSTGRES
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
     let db_pool = PgPool::connect(&database_url).await?;
 
-    // REDIS
-    let redis_host = env::var("REDIS_HOST").expect("HOST is not set in .env file");
-    let redis_port = env::var("REDIS_PORT").expect("PORT is not set in .env file");
-
-    let redis_addr = RedisActor::start(format!("{}:{}", redis_host, redis_port));
-
     let mut server = HttpServer::new(move || {
         // let auth = HttpAuthentication::bearer(validator);
 
@@ -176,12 +369,7 @@ async fn main() -> Result<()> {
             .data(db_pool.clone())
             .data(redis_addr.clone())
             .data(internal_app_data.clone()) // pass database pool to application so we can access it inside handlers
-            .route("/", web::get().to(index))
-            .configure(quality::init)
-            .configure(signs::init)
-            .configure(todo::init)
-            .configure(user::init)
-            .configure(votes::init)
+            .configure(routes)
     });
 
     server = match listenfd.take_tcp_listener(0)? {
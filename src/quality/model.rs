@@ -5,11 +5,10 @@ use futures::future::{ready, Ready};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
 use sqlx::{FromRow, PgPool, Row};
-use strum_macros;
 
 use crate::auth::AuthenticatedUser;
 
-#[derive(Serialize, Deserialize, std::fmt::Debug, strum_macros::ToString, Clone, sqlx::Type)]
+#[derive(Serialize, Deserialize, std::fmt::Debug, strum_macros::Display, Clone, sqlx::Type)]
 #[sqlx(rename = "valid_quality_types")]
 pub enum QualityType {
     Binary,
@@ -17,7 +16,7 @@ pub enum QualityType {
     Multiple,
 }
 
-#[derive(Serialize, Deserialize, std::fmt::Debug, strum_macros::ToString, Clone, sqlx::Type)]
+#[derive(Serialize, Deserialize, std::fmt::Debug, strum_macros::Display, Clone, sqlx::Type)]
 #[sqlx(rename = "quality_defined_by")]
 pub enum QualityDefinedBy {
     System,
@@ -252,11 +251,7 @@ impl Quality {
         let mut qualities_created = vec![];
         let mut tx = pool.begin().await?;
         for q in quality.iter() {
-            let q_type = match q.quality_type {
-                QualityType::Binary => QualityType::Binary,
-                QualityType::Multiple => QualityType::Multiple,
-                QualityType::Percent => QualityType::Percent,
-            };
+            let q_type = q.quality_type.clone();
 
             let q_defined_by = match user.user.is_internal {
                 Some(internal_user) => {
@@ -296,8 +291,8 @@ impl Quality {
             .bind(q.sign_1_id)
             .bind(q.sign_2_id)
             .bind(q_defined_by)
-            .bind(&user.user.id)
-            .bind(&user.user.id)
+            .bind(user.user.id)
+            .bind(user.user.id)
             .map(|row: PgRow| {
                 QualityCreatedOrModified {
                     id: row.get(0),
@@ -336,8 +331,8 @@ impl Quality {
                 )
                 .bind(qu.id)
                 .bind(&c.choice_value)
-                .bind(&user.user.id)
-                .bind(&user.user.id)
+                .bind(user.user.id)
+                .bind(user.user.id)
                 .map(|row: PgRow| Choice {
                     id: row.get(0),
                     choice_value: row.get(1),
@@ -402,11 +397,11 @@ impl Quality {
         )
         .bind(&quality.quality_long)
         .bind(&quality.quality_short)
-        .bind(&quality.sign_1_id)
-        .bind(&quality.sign_2_id)
-        .bind(&user.user.id)
-        .bind(&id)
-        .bind(&user.user.id)
+        .bind(quality.sign_1_id)
+        .bind(quality.sign_2_id)
+        .bind(user.user.id)
+        .bind(id)
+        .bind(user.user.id)
         .map(|row: PgRow| {
             QualityCreatedOrModified {
                 id: row.get(0),
@@ -430,7 +425,7 @@ impl Quality {
         let mut choice_holder = vec![];
 
         // loop over Option of Vectors: https://stackoverflow.com/questions/40907897/iterator-on-optionvec
-        for c in quality.choice.unwrap_or_else(Vec::new).iter() {
+        for c in quality.choice.unwrap_or_default().iter() {
             // if c.id == None {
             //     return Err(anyhow!("Missing attribute: choice_id"));
             // }
@@ -448,10 +443,10 @@ impl Quality {
                     id, choice_value",
             )
             .bind(&c.choice_value)
-            .bind(&user.user.id)
+            .bind(user.user.id)
             .bind(qu.id)
             .bind(c.id)
-            .bind(&user.user.id)
+            .bind(user.user.id)
             .map(|row: PgRow| Choice {
                 id: row.get(0),
                 choice_value: row.get(1),
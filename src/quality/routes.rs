@@ -7,6 +7,7 @@ use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
 use sqlx::PgPool;
 // use uuid::Uuid;
 
+#[allow(dead_code)]
 #[derive(serde::Deserialize)]
 pub struct StartEndOfPayload {
     pub start: i32,
@@ -0,0 +1,164 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::web::Data;
+use async_trait::async_trait;
+use futures::lock::Mutex as AsyncMutex;
+use google_jwt_verify::{
+    download_key_set_async, AsyncKeyProvider, ForcedRefreshThrottle, JsonWebKey, JsonWebKeySet,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{get_redis_key, set_redis_key_with_expiration};
+
+/// Key sets are cached at least this long, even when the endpoint allows none or no caching,
+/// so that verifications do not each download the keys.
+const MIN_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// How long the last good key set keeps being served before a failed download is retried.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize)]
+struct CachedKeySet {
+    jwks: String,
+    expires_at: u64,
+}
+
+struct LocalKeySet {
+    keys: Arc<JsonWebKeySet>,
+    expires_at: u64,
+}
+
+/// Key provider that shares a JWKS between all app instances through Redis.
+///
+/// Keys are looked up in a process local copy first, then in Redis, and are only
+/// downloaded from `url` when the shared entry is missing or expired. Downloaded key
+/// sets are written back to Redis for the lifetime given by the endpoint's
+/// Cache-Control header, but no shorter than a minute. While downloads fail, the last
+/// good key set keeps being served.
+#[derive(Clone)]
+pub struct RedisKeyProvider {
+    url: String,
+    redis_key: String,
+    redis: Data<Addr<RedisActor>>,
+    local: Arc<RwLock<Option<LocalKeySet>>>,
+    refresh_lock: Arc<AsyncMutex<()>>,
+    throttle: ForcedRefreshThrottle,
+}
+
+impl RedisKeyProvider {
+    pub fn new(url: &str, redis_key: &str, redis: Data<Addr<RedisActor>>) -> Self {
+        RedisKeyProvider {
+            url: url.to_string(),
+            redis_key: redis_key.to_string(),
+            redis,
+            local: Arc::new(RwLock::new(None)),
+            refresh_lock: Arc::new(AsyncMutex::new(())),
+            throttle: ForcedRefreshThrottle::new(),
+        }
+    }
+
+    fn local_keys(&self) -> Option<Arc<JsonWebKeySet>> {
+        match *self.local.read().unwrap() {
+            Some(ref local) if current_timestamp() < local.expires_at => Some(local.keys.clone()),
+            _ => None,
+        }
+    }
+
+    fn store_local(&self, jwks: &str, expires_at: u64) -> Result<Arc<JsonWebKeySet>, ()> {
+        let keys: Arc<JsonWebKeySet> = Arc::new(serde_json::from_str(jwks).map_err(|_| ())?);
+        *self.local.write().unwrap() = Some(LocalKeySet {
+            keys: keys.clone(),
+            expires_at,
+        });
+        Ok(keys)
+    }
+
+    /// Loads the shared key set from Redis, downloading it when Redis has none.
+    async fn load_keys(&self) -> Result<Arc<JsonWebKeySet>, ()> {
+        // concurrent misses within this process share a single lookup
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(keys) = self.local_keys() {
+            return Ok(keys);
+        }
+        match get_redis_key::<CachedKeySet>(&self.redis_key, &self.redis).await {
+            Ok(Some(cached)) if current_timestamp() < cached.expires_at => {
+                self.store_local(&cached.jwks, cached.expires_at)
+            }
+            _ => self.download_keys().await,
+        }
+    }
+
+    async fn download_keys(&self) -> Result<Arc<JsonWebKeySet>, ()> {
+        let (jwks, max_age) = match download_key_set_async(&self.url).await {
+            Ok(downloaded) => downloaded,
+            Err(_) => return self.download_failed(),
+        };
+        let max_age = max_age.max(MIN_CACHE_TTL);
+        let expires_at = current_timestamp() + max_age.as_secs();
+        let keys = self.store_local(&jwks, expires_at)?;
+        let cached = serde_json::to_string(&CachedKeySet { jwks, expires_at }).unwrap();
+        // other instances download the keys themselves if this write fails
+        let _ = set_redis_key_with_expiration(
+            self.redis_key.clone(),
+            cached,
+            max_age.as_secs().to_string(),
+            &self.redis,
+        )
+        .await;
+        Ok(keys)
+    }
+
+    /// Falls back to the last good key set, if any, and serves it until the next retry.
+    fn download_failed(&self) -> Result<Arc<JsonWebKeySet>, ()> {
+        warn!("Could not download the key set from {}", self.url);
+        let mut local = self.local.write().unwrap();
+        let local = local.as_mut().ok_or(())?;
+        local.expires_at = current_timestamp() + RETRY_INTERVAL.as_secs();
+        Ok(local.keys.clone())
+    }
+
+    /// Google rotates its keys ahead of the cache expiry, so a token signed with an
+    /// unknown key forces a reload, rate limited like the library's own key provider.
+    async fn refresh_for_unknown_key(&self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(key) = self.local_keys().and_then(|keys| keys.get_key(key_id)) {
+            return Ok(Some(key));
+        }
+        if !self.throttle.try_start() {
+            return Ok(None);
+        }
+        // another instance may already have stored the rotated key set
+        if let Ok(Some(cached)) = get_redis_key::<CachedKeySet>(&self.redis_key, &self.redis).await
+        {
+            let keys = self.store_local(&cached.jwks, cached.expires_at)?;
+            if let Some(key) = keys.get_key(key_id) {
+                return Ok(Some(key));
+            }
+        }
+        Ok(self.download_keys().await?.get_key(key_id))
+    }
+}
+
+#[async_trait]
+impl AsyncKeyProvider for RedisKeyProvider {
+    async fn get_key_async(&self, key_id: &str) -> Result<Option<JsonWebKey>, ()> {
+        let keys = match self.local_keys() {
+            Some(keys) => keys,
+            None => self.load_keys().await?,
+        };
+        match keys.get_key(key_id) {
+            Some(key) => Ok(Some(key)),
+            None => self.refresh_for_unknown_key(key_id).await,
+        }
+    }
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
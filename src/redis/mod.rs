@@ -1,3 +1,9 @@
+mod jwks;
+mod revocation;
+mod token_cache;
 mod util;
 
+pub use jwks::RedisKeyProvider;
+pub use revocation::RedisRevocationList;
+pub use token_cache::{purge_legacy_token_cache, TokenCacheKeys};
 pub use util::*;
@@ -0,0 +1,28 @@
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::web::Data;
+use async_trait::async_trait;
+use google_jwt_verify::AsyncRevocationList;
+
+use super::is_token_revoked;
+
+/// The token deny-list kept in Redis, consulted by the ID token verifiers.
+#[derive(Clone)]
+pub struct RedisRevocationList {
+    redis: Data<Addr<RedisActor>>,
+}
+
+impl RedisRevocationList {
+    pub fn new(redis: Data<Addr<RedisActor>>) -> Self {
+        RedisRevocationList { redis }
+    }
+}
+
+#[async_trait]
+impl AsyncRevocationList for RedisRevocationList {
+    async fn is_revoked_async(&self, revocation_id: &str) -> Result<bool, ()> {
+        is_token_revoked(revocation_id, &self.redis)
+            .await
+            .map_err(|_| ())
+    }
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::web::Data;
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+
+use super::{delete_redis_keys_matching, redis_key_exists, set_redis_key};
+use crate::errors::AppError;
+
+/// Prefix of the entries caching the identity a bearer token was issued for. Bumped whenever
+/// their key or value format changes, so entries of the old format are never read.
+const TOKEN_CACHE_PREFIX: &str = "token-v3";
+
+/// Entries of the first format were keyed by the identity provider and the raw token, such
+/// as `google-<token>`, and held the whole serialized user.
+const LEGACY_TOKEN_CACHE_PATTERNS: [&str; 6] = [
+    "google-*",
+    "facebook-*",
+    "firebase-*",
+    "apple-*",
+    "microsoft-*",
+    "github-*",
+];
+
+/// Set once the legacy entries were purged, so that later boots skip scanning for them.
+const LEGACY_TOKEN_CACHE_PURGED_KEY: &str = "token-cache-legacy-purged";
+
+/// Derives the Redis keys of entries cached per bearer token from an HMAC-SHA256 of the
+/// token, so that reading Redis does not reveal any live credential.
+#[derive(Clone)]
+pub struct TokenCacheKeys {
+    secret: Arc<PKey<Private>>,
+}
+
+impl TokenCacheKeys {
+    pub fn new(secret: &[u8]) -> Result<Self, ErrorStack> {
+        Ok(TokenCacheKeys {
+            secret: Arc::new(PKey::hmac(secret)?),
+        })
+    }
+
+    /// The key of `token`'s entry, namespaced by the identity provider that issued it.
+    pub fn key(&self, idp: &str, token: &str) -> String {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.secret)
+            .expect("HMAC-SHA256 is always available");
+        signer
+            .update(token.as_bytes())
+            .expect("HMAC-SHA256 is always available");
+        let hash = signer
+            .sign_to_vec()
+            .expect("HMAC-SHA256 is always available");
+        let hash: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("{}-{}-{}", TOKEN_CACHE_PREFIX, idp, hash)
+    }
+}
+
+/// Deletes the entries cached under raw tokens, which would otherwise keep live credentials in
+/// Redis until the tokens expire. Returns the number of entries deleted, or `None` when an earlier
+/// purge already completed: no entries of the legacy format are written any more.
+pub async fn purge_legacy_token_cache(
+    redis: &Data<Addr<RedisActor>>,
+) -> Result<Option<i64>, AppError> {
+    if redis_key_exists(LEGACY_TOKEN_CACHE_PURGED_KEY, redis).await? {
+        return Ok(None);
+    }
+    let mut deleted = 0;
+    for pattern in LEGACY_TOKEN_CACHE_PATTERNS.iter() {
+        deleted += delete_redis_keys_matching(pattern, redis).await?;
+    }
+    set_redis_key(LEGACY_TOKEN_CACHE_PURGED_KEY, "1", redis).await?;
+    Ok(Some(deleted))
+}
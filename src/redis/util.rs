@@ -6,6 +6,7 @@ use redis_async::{resp::RespValue, resp_array};
 use crate::errors::AppError;
 
 use serde::de::DeserializeOwned;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub async fn get_redis_key<T>(
     key: &str,
@@ -15,7 +16,7 @@ where
     T: DeserializeOwned,
 {
     // let aed = redis.send(Command(resp_array!["GET", key])).await;
-    return match redis.send(Command(resp_array!["GET", key])).await {
+    match redis.send(Command(resp_array!["GET", key])).await {
         Err(e) => {
             debug!("Redis error #1 from get_redis_key function: {:?}", e);
             Err(AppError::NOT_FOUND.into())
@@ -51,7 +52,27 @@ where
                 Err(AppError::NOT_FOUND.into())
             }
         },
-    };
+    }
+}
+
+pub async fn set_redis_key(
+    key: &str,
+    body: &str,
+    redis: &actix_web::web::Data<Addr<RedisActor>>,
+) -> Result<(), AppError> {
+    match redis.send(Command(resp_array!["SET", key, body])).await {
+        Err(e) => {
+            debug!("Redis error #1 from set_redis_key function: {:?}", e);
+            Err(AppError::NOT_FOUND.into())
+        }
+        Ok(redis_result) => match redis_result {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                debug!("Redis error #2 from set_redis_key function: {:?}", err);
+                Err(AppError::NOT_FOUND.into())
+            }
+        },
+    }
 }
 
 pub async fn set_redis_key_with_expiration(
@@ -89,3 +110,129 @@ pub async fn set_redis_key_with_expiration(
         },
     }
 }
+
+pub async fn redis_key_exists(
+    key: &str,
+    redis: &actix_web::web::Data<Addr<RedisActor>>,
+) -> Result<bool, AppError> {
+    match redis.send(Command(resp_array!["EXISTS", key])).await {
+        Err(e) => {
+            debug!("Redis error #1 from redis_key_exists function: {:?}", e);
+            Err(AppError::NOT_FOUND.into())
+        }
+        Ok(redis_result) => match redis_result {
+            Ok(RespValue::Integer(count)) => Ok(count > 0),
+            Ok(val) => {
+                debug!("Redis error #2 from redis_key_exists function: {:?}", val);
+                Err(AppError::NOT_FOUND.into())
+            }
+            Err(err) => {
+                debug!("Redis error #3 from redis_key_exists function: {:?}", err);
+                Err(AppError::NOT_FOUND.into())
+            }
+        },
+    }
+}
+
+// revoked tokens are keyed by google_jwt_verify::revocation_id, i.e. their issuer and jti, or token hash
+fn revoked_token_key(revocation_id: &str) -> String {
+    format!("revoked-{}", revocation_id)
+}
+
+/// Adds a token to the deny-list until `expires_at`, after which it is rejected as expired anyway.
+pub async fn revoke_token(
+    revocation_id: &str,
+    expires_at: u64,
+    redis: &actix_web::web::Data<Addr<RedisActor>>,
+) -> Result<(), AppError> {
+    let current_timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let ttl_in_seconds = expires_at.saturating_sub(current_timestamp).max(1);
+    set_redis_key_with_expiration(
+        revoked_token_key(revocation_id),
+        "1".to_string(),
+        ttl_in_seconds.to_string(),
+        redis,
+    )
+    .await
+}
+
+pub async fn is_token_revoked(
+    revocation_id: &str,
+    redis: &actix_web::web::Data<Addr<RedisActor>>,
+) -> Result<bool, AppError> {
+    redis_key_exists(&revoked_token_key(revocation_id), redis).await
+}
+
+/// Deletes every key matching `pattern`. Keys are found with SCAN rather than KEYS, which would
+/// block Redis while it walks the whole keyspace.
+pub async fn delete_redis_keys_matching(
+    pattern: &str,
+    redis: &actix_web::web::Data<Addr<RedisActor>>,
+) -> Result<i64, AppError> {
+    let mut cursor = "0".to_string();
+    let mut deleted = 0;
+    loop {
+        let reply = match redis
+            .send(Command(resp_array![
+                "SCAN", &cursor, "MATCH", pattern, "COUNT", "1000"
+            ]))
+            .await
+        {
+            Ok(Ok(reply)) => reply,
+            error => {
+                debug!(
+                    "Redis error #1 from delete_redis_keys_matching function: {:?}",
+                    error
+                );
+                return Err(AppError::NOT_FOUND.into());
+            }
+        };
+        let (next_cursor, keys) = match reply {
+            RespValue::Array(mut reply) if reply.len() == 2 => {
+                match (reply.remove(0), reply.remove(0)) {
+                    (RespValue::BulkString(next_cursor), RespValue::Array(keys)) => {
+                        (String::from_utf8_lossy(&next_cursor).into_owned(), keys)
+                    }
+                    reply => {
+                        debug!(
+                            "Redis error #2 from delete_redis_keys_matching function: {:?}",
+                            reply
+                        );
+                        return Err(AppError::NOT_FOUND.into());
+                    }
+                }
+            }
+            reply => {
+                debug!(
+                    "Redis error #2 from delete_redis_keys_matching function: {:?}",
+                    reply
+                );
+                return Err(AppError::NOT_FOUND.into());
+            }
+        };
+
+        if !keys.is_empty() {
+            let mut command = vec![RespValue::from("DEL")];
+            command.extend(keys);
+            match redis.send(Command(RespValue::Array(command))).await {
+                Ok(Ok(RespValue::Integer(count))) => deleted += count,
+                error => {
+                    debug!(
+                        "Redis error #3 from delete_redis_keys_matching function: {:?}",
+                        error
+                    );
+                    return Err(AppError::NOT_FOUND.into());
+                }
+            }
+        }
+
+        // a full iteration ends when SCAN hands back cursor 0
+        if next_cursor == "0" {
+            return Ok(deleted);
+        }
+        cursor = next_cursor;
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::web::Data;
+use anyhow::Result;
+use google_jwt_verify::{AsyncClient, AsyncKeyProvider, TokenSigner};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Done;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::{
+    auth::{authenticate_identity, TokenIdentity},
+    errors::AppError,
+    idp::{id_token_error, IdentityProvider},
+    redis::RedisRevocationList,
+    GoogleAsyncClient, InternalAppData,
+};
+
+/// The `iss` of our own access tokens.
+pub const SESSION_ISSUER: &str = "astrolytic";
+/// The `aud` of our own access tokens, so tokens signed for anything else are rejected.
+pub const SESSION_AUDIENCE: &str = "astrolytic-api";
+/// Access tokens are short-lived, so that a leaked one is of little use.
+pub const ACCESS_TOKEN_TTL: u64 = 900;
+/// A session ends this long after it was opened, however often it is refreshed.
+pub const SESSION_TTL_DAYS: i32 = 30;
+
+// refresh tokens are hashed in the database, so a leaked table does not leak sessions
+const REFRESH_TOKEN_HASH: &str = "encode(sha256(convert_to($1, 'UTF8')), 'hex')";
+
+// the claims of our own access tokens
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionClaims {
+    pub iss: String,
+    pub aud: String,
+    // the user's UUID
+    pub sub: Uuid,
+    // the session the token was issued for
+    pub sid: Uuid,
+    pub jti: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+// this struct is returned when a session is opened or refreshed
+#[derive(Serialize, Deserialize)]
+pub struct SessionTokens {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    pub refresh_token: String,
+}
+
+// this struct will use to receive user input
+#[derive(Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+}
+
+/// Signs our own access tokens and verifies them on later requests.
+#[derive(Clone)]
+pub struct SessionIssuer {
+    signer: Arc<TokenSigner>,
+    client: Arc<GoogleAsyncClient>,
+}
+
+impl SessionIssuer {
+    pub fn new(signer: TokenSigner, revocation_list: RedisRevocationList) -> Self {
+        let keys: Box<dyn AsyncKeyProvider> = Box::new(signer.key_provider());
+        let client = AsyncClient::builder(SESSION_AUDIENCE)
+            .custom_key_provider(keys)
+            .issuers(&[SESSION_ISSUER])
+            .algorithms(&[signer.get_algorithm()])
+            // reject access tokens revoked through POST /auth/logout
+            .revocation_list(revocation_list)
+            .build();
+        SessionIssuer {
+            signer: Arc::new(signer),
+            client: Arc::new(client),
+        }
+    }
+
+    pub fn issue(
+        &self,
+        session: &Session,
+        refresh_token: String,
+    ) -> Result<SessionTokens, AppError> {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let claims = SessionClaims {
+            iss: SESSION_ISSUER.to_string(),
+            aud: SESSION_AUDIENCE.to_string(),
+            sub: session.user_id,
+            sid: session.id,
+            jti: Uuid::new_v4().to_simple().to_string(),
+            iat: issued_at,
+            exp: issued_at + ACCESS_TOKEN_TTL,
+        };
+        let access_token = self.signer.sign(&claims).map_err(|e| {
+            warn!("Could not sign access token: {}", e);
+            AppError::INTERNAL_ERROR.default()
+        })?;
+
+        Ok(SessionTokens {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: ACCESS_TOKEN_TTL,
+            refresh_token,
+        })
+    }
+
+    // an access token is only valid while its session is, so logging out or reusing a refresh
+    // token also ends the access tokens issued before
+    pub async fn verify(&self, token: &str, pool: &PgPool) -> Result<SessionClaims, AppError> {
+        let token = self
+            .client
+            .verify_token_with_payload_async::<SessionClaims>(token)
+            .await
+            .map_err(|e| id_token_error("Session", e))?;
+        let claims = token.get_payload().clone();
+        if !Session::is_live(claims.sid, pool).await? {
+            debug!("Session {} has ended", claims.sid);
+            return Err(AppError::NOT_AUTHORIZED.message("session has ended".to_string()));
+        }
+        Ok(claims)
+    }
+}
+
+fn new_refresh_token() -> String {
+    format!(
+        "{}{}",
+        Uuid::new_v4().to_simple(),
+        Uuid::new_v4().to_simple()
+    )
+}
+
+impl Session {
+    // open a session for the user signing in with an identity provider's token
+    pub async fn open(
+        provider: &dyn IdentityProvider,
+        token: &str,
+        internal_app_data: &InternalAppData,
+        pool: &PgPool,
+        redis: &Data<Addr<RedisActor>>,
+    ) -> Result<SessionTokens, AppError> {
+        let (user, identity) = authenticate_identity(
+            provider,
+            token,
+            &internal_app_data.token_cache_keys,
+            pool,
+            redis,
+        )
+        .await?;
+        let (session, refresh_token) = Session::create(user.user.id, &identity, pool).await?;
+        internal_app_data.sessions.issue(&session, refresh_token)
+    }
+
+    // exchange a refresh token for a new access token and the next refresh token
+    pub async fn refresh(
+        request: RefreshRequest,
+        internal_app_data: &InternalAppData,
+        pool: &PgPool,
+    ) -> Result<SessionTokens, AppError> {
+        match Session::rotate(&request.refresh_token, pool).await? {
+            Some((session, refresh_token)) => {
+                internal_app_data.sessions.issue(&session, refresh_token)
+            }
+            None => Err(AppError::NOT_AUTHORIZED
+                .message("refresh token is invalid, expired or revoked".to_string())),
+        }
+    }
+
+    // open a session for the user, signed in with the identity. Unlinking the identity ends it.
+    pub async fn create(
+        user_id: Uuid,
+        identity: &TokenIdentity,
+        pool: &PgPool,
+    ) -> Result<(Session, String)> {
+        let mut tx = pool.begin().await?;
+        let session = sqlx::query(
+            "INSERT INTO SESSIONS (USER_ID, EXPIRES_AT, PROVIDER, SUBJECT)
+                VALUES ($1, current_timestamp + make_interval(days => $2), $3, $4)
+                RETURNING ID, USER_ID",
+        )
+        .bind(user_id)
+        .bind(SESSION_TTL_DAYS)
+        .bind(&identity.provider)
+        .bind(&identity.subject)
+        .map(|row: PgRow| Session {
+            id: row.get(0),
+            user_id: row.get(1),
+        })
+        .fetch_one(&mut tx)
+        .await?;
+
+        let refresh_token = new_refresh_token();
+        sqlx::query(&format!(
+            "INSERT INTO SESSION_REFRESH_TOKENS (TOKEN_HASH, SESSION_ID) VALUES ({}, $2)",
+            REFRESH_TOKEN_HASH
+        ))
+        .bind(&refresh_token)
+        .bind(session.id)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok((session, refresh_token))
+    }
+
+    // use up a refresh token of a live session and issue the next one. A token that was already
+    // used is being replayed, e.g. after it leaked, so the whole session is revoked.
+    pub async fn rotate(refresh_token: &str, pool: &PgPool) -> Result<Option<(Session, String)>> {
+        let mut tx = pool.begin().await?;
+        let session = sqlx::query(&format!(
+            "UPDATE SESSION_REFRESH_TOKENS t SET USED_AT = current_timestamp
+                FROM SESSIONS s
+                where t.TOKEN_HASH = {} and t.USED_AT is null and s.ID = t.SESSION_ID
+                    and s.REVOKED_AT is null and s.EXPIRES_AT > current_timestamp
+                RETURNING s.ID, s.USER_ID",
+            REFRESH_TOKEN_HASH
+        ))
+        .bind(refresh_token)
+        .map(|row: PgRow| Session {
+            id: row.get(0),
+            user_id: row.get(1),
+        })
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let session = match session {
+            Some(session) => session,
+            None => {
+                let revoked = sqlx::query(&format!(
+                    "UPDATE SESSIONS set REVOKED_AT = current_timestamp, UPDATED_AT = current_timestamp
+                        where REVOKED_AT is null and ID = (SELECT SESSION_ID FROM SESSION_REFRESH_TOKENS
+                            where TOKEN_HASH = {} and USED_AT is not null)",
+                    REFRESH_TOKEN_HASH
+                ))
+                .bind(refresh_token)
+                .execute(&mut tx)
+                .await?;
+                tx.commit().await?;
+
+                if revoked.rows_affected() > 0 {
+                    warn!("Refresh token reused, session revoked");
+                }
+                return Ok(None);
+            }
+        };
+
+        let next_refresh_token = new_refresh_token();
+        sqlx::query(&format!(
+            "INSERT INTO SESSION_REFRESH_TOKENS (TOKEN_HASH, SESSION_ID) VALUES ({}, $2)",
+            REFRESH_TOKEN_HASH
+        ))
+        .bind(&next_refresh_token)
+        .bind(session.id)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query("UPDATE SESSIONS set UPDATED_AT = current_timestamp where ID = $1")
+            .bind(session.id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(Some((session, next_refresh_token)))
+    }
+
+    pub async fn is_live(id: Uuid, pool: &PgPool) -> Result<bool> {
+        let live = sqlx::query(
+            "SELECT ID FROM SESSIONS
+                where ID = $1 and REVOKED_AT is null and EXPIRES_AT > current_timestamp",
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(live.is_some())
+    }
+
+    // end the session a refresh token belongs to. Returns false if it was already ended.
+    pub async fn revoke(refresh_token: &str, pool: &PgPool) -> Result<bool> {
+        let revoked = sqlx::query(&format!(
+            "UPDATE SESSIONS set REVOKED_AT = current_timestamp, UPDATED_AT = current_timestamp
+                where REVOKED_AT is null and ID = (SELECT SESSION_ID FROM SESSION_REFRESH_TOKENS
+                    where TOKEN_HASH = {})",
+            REFRESH_TOKEN_HASH
+        ))
+        .bind(refresh_token)
+        .execute(pool)
+        .await?;
+
+        Ok(revoked.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{
+    session::{RefreshRequest, Session},
+    InternalAppData,
+};
+use actix::prelude::*;
+use actix_redis::RedisActor;
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use sqlx::PgPool;
+
+// exchange an identity provider's token, selected by the `idp` header, for our own session tokens
+#[post("/auth/session")]
+async fn create(
+    req: HttpRequest,
+    bearer: BearerAuth,
+    internal_app_data: web::Data<InternalAppData>,
+    db_pool: web::Data<PgPool>,
+    redis: web::Data<Addr<RedisActor>>,
+) -> impl Responder {
+    let provider = req
+        .headers()
+        .get("idp")
+        .and_then(|idp| idp.to_str().ok())
+        .and_then(|idp| internal_app_data.identity_providers.get(idp));
+    let provider = match provider {
+        Some(provider) => provider,
+        None => return HttpResponse::BadRequest().body("Unknown or missing idp header"),
+    };
+
+    let result = Session::open(
+        provider.as_ref(),
+        bearer.token(),
+        internal_app_data.get_ref(),
+        db_pool.get_ref(),
+        &redis,
+    )
+    .await;
+
+    match result {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(e) => {
+            debug!(
+                "Error occurred in Session >> routes.rs create function: \n{}",
+                e
+            );
+            e.error_response()
+        }
+    }
+}
+
+#[post("/auth/refresh")]
+async fn refresh(
+    request: web::Json<RefreshRequest>,
+    internal_app_data: web::Data<InternalAppData>,
+    db_pool: web::Data<PgPool>,
+) -> impl Responder {
+    let result = Session::refresh(
+        request.into_inner(),
+        internal_app_data.get_ref(),
+        db_pool.get_ref(),
+    )
+    .await;
+
+    match result {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(e) => {
+            debug!(
+                "Error occurred in Session >> routes.rs refresh function: \n{}",
+                e
+            );
+            e.error_response()
+        }
+    }
+}
+
+// end the session of the refresh token, along with the access tokens issued for it
+#[post("/auth/logout")]
+async fn logout(request: web::Json<RefreshRequest>, db_pool: web::Data<PgPool>) -> impl Responder {
+    let result = Session::revoke(&request.refresh_token, db_pool.get_ref()).await;
+    match result {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            debug!(
+                "Error occurred in Session >> routes.rs logout function: \n{}",
+                e
+            );
+            HttpResponse::BadRequest().body("Error while ending session")
+        }
+    }
+}
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(create);
+    cfg.service(refresh);
+    cfg.service(logout);
+}
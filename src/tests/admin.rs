@@ -0,0 +1,89 @@
+use actix_web::{http::StatusCode, rt as actix_rt, test};
+use google_jwt_verify::decode_unverified;
+use serde_json::json;
+
+use super::{signed_in, TestAccount, TestContext};
+use crate::idp::APPLE_ISSUER;
+
+#[actix_rt::test]
+async fn test_only_sysadmin_revokes_tokens() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let sysadmin = TestAccount {
+        email: context.internal_app_data.sysadmin.clone(),
+        ..TestAccount::new()
+    };
+    let sysadmin_token = context.google_token(&sysadmin);
+    let user_token = context.google_token(&TestAccount::new());
+    let revoked_token = context.apple_token(&TestAccount::new());
+    context.authenticate("Apple", &revoked_token).await;
+    let expires_at = decode_unverified(&revoked_token).unwrap().get_claims()["exp"].clone();
+    let revocation = json!({ "token": revoked_token, "expires_at": expires_at });
+
+    let request = signed_in(test::TestRequest::post(), Some("Google"), &user_token)
+        .uri("/admin/revocations")
+        .set_json(&revocation);
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = signed_in(test::TestRequest::get(), Some("Apple"), &revoked_token)
+        .uri("/user/me")
+        .to_request();
+    assert_eq!(
+        test::call_service(&mut service, request).await.status(),
+        StatusCode::OK
+    );
+
+    let request = signed_in(test::TestRequest::post(), Some("Google"), &sysadmin_token)
+        .uri("/admin/revocations")
+        .set_json(&revocation);
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let request = signed_in(test::TestRequest::get(), Some("Apple"), &revoked_token)
+        .uri("/user/me")
+        .to_request();
+    assert_eq!(
+        test::call_service(&mut service, request).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_rt::test]
+async fn test_revocation_by_issuer_and_jti() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let sysadmin = TestAccount {
+        email: context.internal_app_data.sysadmin.clone(),
+        ..TestAccount::new()
+    };
+    let sysadmin_token = context.google_token(&sysadmin);
+    let revoked_token = context.apple_token(&TestAccount::new());
+    context.authenticate("Apple", &revoked_token).await;
+    let claims = decode_unverified(&revoked_token)
+        .unwrap()
+        .get_claims()
+        .clone();
+
+    // a jti is only unique per issuer
+    let request = signed_in(test::TestRequest::post(), Some("Google"), &sysadmin_token)
+        .uri("/admin/revocations")
+        .set_json(&json!({ "jti": claims["jti"], "expires_at": claims["exp"] }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = signed_in(test::TestRequest::post(), Some("Google"), &sysadmin_token)
+        .uri("/admin/revocations")
+        .set_json(&json!({
+            "issuer": APPLE_ISSUER,
+            "jti": claims["jti"],
+            "expires_at": claims["exp"],
+        }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let request = signed_in(test::TestRequest::get(), Some("Apple"), &revoked_token)
+        .uri("/user/me")
+        .to_request();
+    assert_eq!(
+        test::call_service(&mut service, request).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
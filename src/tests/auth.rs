@@ -0,0 +1,108 @@
+use actix_web::{http::StatusCode, rt as actix_rt, test};
+use google_jwt_verify::decode_unverified;
+use serde_json::json;
+
+use super::{current_user_id, signed_in, TestAccount, TestContext};
+use crate::{
+    redis::revoke_token,
+    user::{User, UserExternalIDP},
+};
+
+#[actix_rt::test]
+async fn test_first_login_provisions_user() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let account = TestAccount::new();
+    let token = context.google_token(&account);
+
+    let request = signed_in(test::TestRequest::get(), Some("Google"), &token);
+    let user_id = current_user_id(&mut service, request).await;
+
+    let user = User::find_by_idp_id(&UserExternalIDP::Google, &account.subject, &context.db_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.id, user_id);
+    assert_eq!(user.email, Some(account.email));
+    assert_eq!(user.sign_id, None);
+
+    // the cached token signs in to the same user
+    let request = signed_in(test::TestRequest::get(), Some("Google"), &token);
+    assert_eq!(current_user_id(&mut service, request).await, user_id);
+}
+
+#[actix_rt::test]
+async fn test_concurrent_first_logins_with_same_email() {
+    let context = TestContext::new().await;
+    let first = TestAccount::new();
+    let second = TestAccount {
+        email: first.email.clone(),
+        ..TestAccount::new()
+    };
+    let first_token = context.google_token(&first);
+    let second_token = context.apple_token(&second);
+
+    let (first_user, second_user) = futures::join!(
+        context.authenticate("Google", &first_token),
+        context.authenticate("Apple", &second_token)
+    );
+
+    // both logins succeed, and only one of the users gets the email
+    assert_ne!(first_user.user.id, second_user.user.id);
+    let emails = [first_user.user.email, second_user.user.email];
+    assert_eq!(emails.iter().filter(|email| email.is_some()).count(), 1);
+}
+
+#[actix_rt::test]
+async fn test_revoked_token_rejected_after_caching() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let account = TestAccount::new();
+    let token = context.google_token(&account);
+    context.authenticate("Google", &token).await;
+
+    let expires_at = decode_unverified(&token).unwrap().get_claims()["exp"]
+        .as_u64()
+        .unwrap();
+    revoke_token(
+        &google_jwt_verify::revocation_id(&token),
+        expires_at,
+        &context.redis,
+    )
+    .await
+    .unwrap();
+
+    let request = signed_in(test::TestRequest::get(), Some("Google"), &token);
+    let response = test::call_service(&mut service, request.uri("/user/me").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn test_free_user_only_on_get() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let free_user = User::find_by_email_id(&context.internal_app_data.dummy_user, &context.db_pool)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // no credential at all
+    assert_eq!(
+        current_user_id(&mut service, test::TestRequest::get()).await,
+        free_user.id
+    );
+    let request = test::TestRequest::post()
+        .uri("/user/onboarding")
+        .set_json(&json!({ "sign_id": 0 }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // a bearer token without an `idp` header that is none of our access tokens
+    let request = signed_in(test::TestRequest::get(), None, "not-a-session-token");
+    assert_eq!(current_user_id(&mut service, request).await, free_user.id);
+    let request = signed_in(test::TestRequest::post(), None, "not-a-session-token")
+        .uri("/user/onboarding")
+        .set_json(&json!({ "sign_id": 0 }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,123 @@
+use actix_web::{http::StatusCode, rt as actix_rt, test};
+use serde_json::json;
+
+use super::{current_user_id, open_session, signed_in, TestAccount, TestContext};
+use crate::{
+    session::SessionTokens,
+    user::{User, UserExternalIDP},
+};
+
+#[actix_rt::test]
+async fn test_link_and_unlink() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let google_token = context.google_token(&TestAccount::new());
+    let apple_account = TestAccount::new();
+    let apple_token = context.apple_token(&apple_account);
+    let user = context.authenticate("Google", &google_token).await;
+
+    let request = signed_in(test::TestRequest::post(), Some("Google"), &google_token)
+        .uri("/user/identities")
+        .set_json(&json!({ "idp": "Apple", "token": apple_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // either identity signs in to the user
+    let request = signed_in(test::TestRequest::get(), Some("Apple"), &apple_token);
+    assert_eq!(current_user_id(&mut service, request).await, user.user.id);
+    let request = signed_in(test::TestRequest::get(), Some("Google"), &google_token)
+        .uri("/user/identities")
+        .to_request();
+    let identities: Vec<serde_json::Value> = test::read_response_json(&mut service, request).await;
+    assert_eq!(identities.len(), 2);
+
+    // the proof token is revoked on unlink, so it is not the one signed in with below
+    let session = open_session(&mut service, "Apple", &apple_token).await;
+    let proof = context.apple_token(&apple_account);
+    let request = signed_in(test::TestRequest::delete(), Some("Google"), &google_token)
+        .uri("/user/identities")
+        .set_json(&json!({ "idp": "Apple", "token": proof }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // the unlinked identity's cached token and sessions no longer sign in to the user; the
+    // token signs in to an account of the identity's own
+    let request = signed_in(test::TestRequest::get(), Some("Apple"), &apple_token);
+    assert_ne!(current_user_id(&mut service, request).await, user.user.id);
+    let request = test::TestRequest::post()
+        .uri("/auth/refresh")
+        .set_json(&json!({ "refresh_token": session.refresh_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = signed_in(test::TestRequest::get(), None, &session.access_token);
+    assert_ne!(current_user_id(&mut service, request).await, user.user.id);
+
+    // the last identity is kept
+    let request = signed_in(test::TestRequest::delete(), Some("Google"), &google_token)
+        .uri("/user/identities")
+        .set_json(&json!({ "idp": "Google", "token": google_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_link_merges_user_not_onboarded() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let google_token = context.google_token(&TestAccount::new());
+    let apple_account = TestAccount::new();
+    let apple_token = context.apple_token(&apple_account);
+    let user = context.authenticate("Google", &google_token).await;
+    // signing in with the other identity first provisioned a user of its own
+    let orphan = context.authenticate("Apple", &apple_token).await;
+    let orphan_session = open_session(&mut service, "Apple", &apple_token).await;
+
+    let request = signed_in(test::TestRequest::post(), Some("Google"), &google_token)
+        .uri("/user/identities")
+        .set_json(&json!({ "idp": "Apple", "token": apple_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert!(User::find_by_uuid(orphan.user.id, &context.db_pool)
+        .await
+        .unwrap()
+        .is_none());
+    let merged = User::find_by_idp_id(
+        &UserExternalIDP::Apple,
+        &apple_account.subject,
+        &context.db_pool,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(merged.id, user.user.id);
+    // the session opened by the merged user continues as this user
+    let request = test::TestRequest::post()
+        .uri("/auth/refresh")
+        .set_json(&json!({ "refresh_token": orphan_session.refresh_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokens: SessionTokens = test::read_body_json(response).await;
+    let request = signed_in(test::TestRequest::get(), None, &tokens.access_token);
+    assert_eq!(current_user_id(&mut service, request).await, user.user.id);
+}
+
+#[actix_rt::test]
+async fn test_link_rejects_onboarded_user() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let google_token = context.google_token(&TestAccount::new());
+    let apple_token = context.apple_token(&TestAccount::new());
+    context.authenticate("Google", &google_token).await;
+    let other = context.authenticate("Apple", &apple_token).await;
+    context.onboard(other.user.id).await;
+
+    let request = signed_in(test::TestRequest::post(), Some("Google"), &google_token)
+        .uri("/user/identities")
+        .set_json(&json!({ "idp": "Apple", "token": apple_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = signed_in(test::TestRequest::get(), Some("Apple"), &apple_token);
+    assert_eq!(current_user_id(&mut service, request).await, other.user.id);
+}
@@ -0,0 +1,240 @@
+//! Integration tests of the HTTP API. They run against the Postgres database and Redis server
+//! configured in .env, like the app itself, with identity providers verifying tokens minted by
+//! `google_jwt_verify::testing::TestIssuer` instead of Google's and Apple's.
+
+mod admin;
+mod auth;
+mod identities;
+mod redis;
+mod session;
+
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix::prelude::*;
+use actix_http::Request;
+use actix_redis::RedisActor;
+use actix_web::{
+    dev::{Service, ServiceResponse},
+    test,
+    web::Data,
+    App, Error,
+};
+use dotenv::dotenv;
+use google_jwt_verify::{
+    testing::TestIssuer, Algorithm, AsyncClient, AsyncKeyProvider, TokenInfoClient, TokenSigner,
+};
+use serde_json::json;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::{
+    auth::{authenticate, AuthenticatedUser},
+    idp::{AppleProvider, GoogleProvider, IdentityProviders, APPLE_ISSUER},
+    redis::{RedisRevocationList, TokenCacheKeys},
+    routes,
+    session::{SessionIssuer, SessionTokens},
+    InternalAppData,
+};
+
+const GOOGLE_CLIENT_ID: &str = "test.apps.googleusercontent.com";
+const APPLE_CLIENT_ID: &str = "com.example.astrolytic";
+const FREE_USER: &str = "free-user@example.com";
+
+/// A verified email and a subject of its own, so that tests sharing the database do not meet.
+pub struct TestAccount {
+    pub subject: String,
+    pub email: String,
+}
+
+impl TestAccount {
+    pub fn new() -> Self {
+        let id = Uuid::new_v4().to_simple().to_string();
+        TestAccount {
+            email: format!("{}@example.com", id),
+            subject: id,
+        }
+    }
+}
+
+pub struct TestContext {
+    pub google: TestIssuer,
+    pub apple: TestIssuer,
+    pub internal_app_data: InternalAppData,
+    pub db_pool: PgPool,
+    pub redis: Data<Addr<RedisActor>>,
+}
+
+impl TestContext {
+    pub async fn new() -> Self {
+        dotenv().ok();
+        let db_pool = PgPool::connect(&env::var("DATABASE_URL").expect("DATABASE_URL is not set"))
+            .await
+            .expect("could not connect to the database");
+        let redis = Data::new(RedisActor::start(format!(
+            "{}:{}",
+            env::var("REDIS_HOST").expect("REDIS_HOST is not set"),
+            env::var("REDIS_PORT").expect("REDIS_PORT is not set")
+        )));
+
+        let google = TestIssuer::new();
+        let google_keys: Box<dyn AsyncKeyProvider> = Box::new(google.key_provider());
+        let google_client = AsyncClient::builder(GOOGLE_CLIENT_ID)
+            .custom_key_provider(google_keys)
+            .algorithms(&[Algorithm::RS256])
+            .build();
+        let apple = TestIssuer::new();
+        let apple_keys: Box<dyn AsyncKeyProvider> = Box::new(apple.key_provider());
+        let apple_client = AsyncClient::builder(APPLE_CLIENT_ID)
+            .custom_key_provider(apple_keys)
+            .issuers(&[APPLE_ISSUER])
+            .algorithms(&[Algorithm::RS256])
+            .build();
+
+        let mut identity_providers = IdentityProviders::new();
+        identity_providers.register(
+            "Google",
+            GoogleProvider::new(
+                google_client,
+                TokenInfoClient::builder(GOOGLE_CLIENT_ID).build(),
+                vec![],
+            ),
+        );
+        identity_providers.register("Apple", AppleProvider::new(apple_client));
+
+        // GET requests without a credential run as this user
+        sqlx::query(
+            "INSERT INTO USERS (EXTERNAL_IDP, EXTERNAL_IDP_ID, EMAIL) VALUES ('Google', $1, $1)
+                ON CONFLICT (EMAIL) DO NOTHING",
+        )
+        .bind(FREE_USER)
+        .execute(&db_pool)
+        .await
+        .unwrap();
+
+        let internal_app_data = InternalAppData {
+            identity_providers,
+            sessions: SessionIssuer::new(
+                TokenSigner::generate("session").unwrap(),
+                RedisRevocationList::new(redis.clone()),
+            ),
+            token_cache_keys: TokenCacheKeys::new(Uuid::new_v4().as_bytes()).unwrap(),
+            sysadmin: TestAccount::new().email,
+            dummy_user: FREE_USER.to_string(),
+        };
+
+        TestContext {
+            google,
+            apple,
+            internal_app_data,
+            db_pool,
+            redis,
+        }
+    }
+
+    /// The app with all its routes, as served by main.
+    pub async fn service(
+        &self,
+    ) -> impl Service<Request = Request, Response = ServiceResponse, Error = Error> {
+        test::init_service(
+            App::new()
+                .data(self.db_pool.clone())
+                .data(self.redis.get_ref().clone())
+                .data(self.internal_app_data.clone())
+                .configure(routes),
+        )
+        .await
+    }
+
+    pub fn google_token(&self, account: &TestAccount) -> String {
+        self.google
+            .google_id_token(GOOGLE_CLIENT_ID, &account.subject, &account.email)
+    }
+
+    pub fn apple_token(&self, account: &TestAccount) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.apple.sign(&json!({
+            "iss": APPLE_ISSUER,
+            "aud": APPLE_CLIENT_ID,
+            "sub": account.subject,
+            "email": account.email,
+            "email_verified": "true",
+            "iat": now,
+            "exp": now + 600,
+            "jti": Uuid::new_v4().to_simple().to_string(),
+        }))
+    }
+
+    /// Signs in with the token, provisioning its user on first login.
+    pub async fn authenticate(&self, idp: &str, token: &str) -> AuthenticatedUser {
+        let provider = self.internal_app_data.identity_providers.get(idp).unwrap();
+        authenticate(
+            provider.as_ref(),
+            token,
+            &self.internal_app_data.token_cache_keys,
+            &self.db_pool,
+            &self.redis,
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Completes the onboarding of a user provisioned on first login.
+    pub async fn onboard(&self, user_id: Uuid) {
+        let sign_id: i32 = sqlx::query(
+            "INSERT INTO SIGN (SIGN_NAME) VALUES ('Test')
+                ON CONFLICT (SIGN_NAME) DO UPDATE SET SIGN_NAME = EXCLUDED.SIGN_NAME
+                RETURNING ID",
+        )
+        .map(|row: sqlx::postgres::PgRow| row.get(0))
+        .fetch_one(&self.db_pool)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE USERS set SIGN_ID = $1 where ID = $2")
+            .bind(sign_id)
+            .bind(user_id)
+            .execute(&self.db_pool)
+            .await
+            .unwrap();
+    }
+}
+
+/// Adds the `Authorization` header, and the `idp` header of identity provider tokens.
+pub fn signed_in(request: test::TestRequest, idp: Option<&str>, token: &str) -> test::TestRequest {
+    let request = request.header("Authorization", format!("Bearer {}", token));
+    match idp {
+        Some(idp) => request.header("idp", idp),
+        None => request,
+    }
+}
+
+/// Opens a session with the identity provider's token through POST /auth/session.
+pub async fn open_session<S>(service: &mut S, idp: &str, token: &str) -> SessionTokens
+where
+    S: Service<Request = Request, Response = ServiceResponse, Error = Error>,
+{
+    let request = signed_in(
+        test::TestRequest::post().uri("/auth/session"),
+        Some(idp),
+        token,
+    );
+    let response = test::call_service(service, request.to_request()).await;
+    assert!(response.status().is_success(), "{:?}", response.status());
+    test::read_body_json(response).await
+}
+
+/// The ID of the user GET /user/me runs as: the credential's user, or else the free user.
+pub async fn current_user_id<S>(service: &mut S, request: test::TestRequest) -> Uuid
+where
+    S: Service<Request = Request, Response = ServiceResponse, Error = Error>,
+{
+    let response = test::call_service(service, request.uri("/user/me").to_request()).await;
+    assert!(response.status().is_success(), "{:?}", response.status());
+    let user: serde_json::Value = test::read_body_json(response).await;
+    user["id"].as_str().unwrap().parse().unwrap()
+}
@@ -0,0 +1,138 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{rt as actix_rt, test, web, App, Error, HttpResponse};
+use google_jwt_verify::{testing::TestIssuer, AsyncKeyProvider};
+use uuid::Uuid;
+
+use super::TestContext;
+use crate::redis::{
+    delete_redis_keys_matching, get_redis_key, purge_legacy_token_cache, redis_key_exists,
+    set_redis_key_with_expiration, RedisKeyProvider,
+};
+
+/// Serves `jwks` without allowing it to be cached, until `available` is cleared.
+fn jwks_server(jwks: String, available: Arc<AtomicBool>) -> test::TestServer {
+    test::start(move || {
+        let jwks = jwks.clone();
+        let available = available.clone();
+        App::new().route(
+            "/jwks",
+            web::get().to(move || {
+                let response = if available.load(Ordering::SeqCst) {
+                    HttpResponse::Ok()
+                        .header("Cache-Control", "no-cache")
+                        .body(jwks.clone())
+                } else {
+                    HttpResponse::ServiceUnavailable().finish()
+                };
+                async move { Ok::<_, Error>(response) }
+            }),
+        )
+    })
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[actix_rt::test]
+async fn test_redis_key_provider_shares_keys() {
+    let context = TestContext::new().await;
+    let issuer = TestIssuer::new();
+    let available = Arc::new(AtomicBool::new(true));
+    let server = jwks_server(issuer.get_jwks().to_string(), available.clone());
+    let redis_key = format!("jwks-test-{}", Uuid::new_v4());
+
+    let provider = RedisKeyProvider::new(&server.url("/jwks"), &redis_key, context.redis.clone());
+    let key = provider.get_key_async(issuer.get_key_id()).await.unwrap();
+    assert!(key.is_some());
+
+    // cached for at least a minute, although the endpoint allows no caching
+    let cached = get_redis_key::<serde_json::Value>(&redis_key, &context.redis)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(cached["expires_at"].as_u64().unwrap() >= current_timestamp() + 59);
+
+    // another instance reads the keys from Redis instead of downloading them
+    available.store(false, Ordering::SeqCst);
+    let other = RedisKeyProvider::new(&server.url("/jwks"), &redis_key, context.redis.clone());
+    let key = other.get_key_async(issuer.get_key_id()).await.unwrap();
+    assert!(key.is_some());
+}
+
+#[actix_rt::test]
+async fn test_redis_key_provider_download_failure() {
+    let context = TestContext::new().await;
+    let issuer = TestIssuer::new();
+    let available = Arc::new(AtomicBool::new(false));
+    let server = jwks_server(issuer.get_jwks().to_string(), available.clone());
+    let redis_key = format!("jwks-test-{}", Uuid::new_v4());
+    let provider = RedisKeyProvider::new(&server.url("/jwks"), &redis_key, context.redis.clone());
+
+    // without a key set to fall back to, the failure is reported
+    assert!(provider.get_key_async(issuer.get_key_id()).await.is_err());
+
+    available.store(true, Ordering::SeqCst);
+    assert!(provider
+        .get_key_async(issuer.get_key_id())
+        .await
+        .unwrap()
+        .is_some());
+
+    // an unknown key forces a download, which fails, and the last good key set is kept
+    available.store(false, Ordering::SeqCst);
+    let unknown = provider.get_key_async("unknown-key").await.unwrap();
+    assert!(unknown.is_none());
+    assert!(provider
+        .get_key_async(issuer.get_key_id())
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[actix_rt::test]
+async fn test_purge_legacy_token_cache_once() {
+    let context = TestContext::new().await;
+    let purged_marker = "token-cache-legacy-purged";
+    delete_redis_keys_matching(purged_marker, &context.redis)
+        .await
+        .unwrap();
+
+    let legacy_key = format!("google-{}", Uuid::new_v4());
+    set_redis_key_with_expiration(
+        legacy_key.clone(),
+        "{}".to_string(),
+        "60".to_string(),
+        &context.redis,
+    )
+    .await
+    .unwrap();
+    let deleted = purge_legacy_token_cache(&context.redis).await.unwrap();
+    assert!(deleted.unwrap() >= 1);
+    assert!(!redis_key_exists(&legacy_key, &context.redis).await.unwrap());
+
+    // later boots skip the scan
+    set_redis_key_with_expiration(
+        legacy_key.clone(),
+        "{}".to_string(),
+        "60".to_string(),
+        &context.redis,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        purge_legacy_token_cache(&context.redis).await.unwrap(),
+        None
+    );
+    assert!(redis_key_exists(&legacy_key, &context.redis).await.unwrap());
+}
@@ -0,0 +1,88 @@
+use actix_web::{http::StatusCode, rt as actix_rt, test};
+use serde_json::json;
+
+use super::{current_user_id, open_session, signed_in, TestAccount, TestContext};
+use crate::session::SessionTokens;
+
+#[actix_rt::test]
+async fn test_session_access_token() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let account = TestAccount::new();
+    let token = context.google_token(&account);
+    let user = context.authenticate("Google", &token).await;
+
+    let tokens = open_session(&mut service, "Google", &token).await;
+    assert_eq!(tokens.token_type, "Bearer");
+
+    let request = signed_in(test::TestRequest::get(), None, &tokens.access_token);
+    assert_eq!(current_user_id(&mut service, request).await, user.user.id);
+}
+
+#[actix_rt::test]
+async fn test_refresh_token_rotation_and_reuse() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let account = TestAccount::new();
+    let token = context.google_token(&account);
+    let user = context.authenticate("Google", &token).await;
+    let tokens = open_session(&mut service, "Google", &token).await;
+
+    let request = test::TestRequest::post()
+        .uri("/auth/refresh")
+        .set_json(&json!({ "refresh_token": tokens.refresh_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let rotated: SessionTokens = test::read_body_json(response).await;
+    assert_ne!(rotated.refresh_token, tokens.refresh_token);
+    let request = signed_in(test::TestRequest::get(), None, &rotated.access_token);
+    assert_eq!(current_user_id(&mut service, request).await, user.user.id);
+
+    // replaying the used refresh token revokes the session, and with it the rotated tokens
+    let request = test::TestRequest::post()
+        .uri("/auth/refresh")
+        .set_json(&json!({ "refresh_token": tokens.refresh_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = test::TestRequest::post()
+        .uri("/auth/refresh")
+        .set_json(&json!({ "refresh_token": rotated.refresh_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = signed_in(test::TestRequest::post(), None, &rotated.access_token)
+        .uri("/user/onboarding")
+        .set_json(&json!({ "sign_id": 0 }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn test_logout_ends_access_tokens() {
+    let context = TestContext::new().await;
+    let mut service = context.service().await;
+    let account = TestAccount::new();
+    let token = context.google_token(&account);
+    let user = context.authenticate("Google", &token).await;
+    let tokens = open_session(&mut service, "Google", &token).await;
+
+    let request = test::TestRequest::post()
+        .uri("/auth/logout")
+        .set_json(&json!({ "refresh_token": tokens.refresh_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // the access token is rejected, and GET requests with it run as the free user
+    let request = signed_in(test::TestRequest::post(), None, &tokens.access_token)
+        .uri("/user/onboarding")
+        .set_json(&json!({ "sign_id": 0 }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = signed_in(test::TestRequest::get(), None, &tokens.access_token);
+    assert_ne!(current_user_id(&mut service, request).await, user.user.id);
+
+    let request = test::TestRequest::post()
+        .uri("/auth/refresh")
+        .set_json(&json!({ "refresh_token": tokens.refresh_token }));
+    let response = test::call_service(&mut service, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
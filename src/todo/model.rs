@@ -67,7 +67,7 @@ impl Todo {
                 "#,
             id
         )
-        .fetch_one(&*pool)
+        .fetch_one(pool)
         .await?;
 
         Ok(Todo {
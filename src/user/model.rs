@@ -5,7 +5,7 @@ use futures::future::{ready, Ready};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
 use sqlx::Done;
-use sqlx::{FromRow, PgPool, Row};
+use sqlx::{FromRow, PgPool, Postgres, Row, Transaction};
 use std::fmt;
 // use strum_macros;
 use uuid::Uuid;
@@ -17,6 +17,10 @@ use crate::auth::AuthenticatedUser;
 pub enum UserExternalIDP {
     Google,
     Facebook,
+    Firebase,
+    Apple,
+    Microsoft,
+    GitHub,
     // Twitter,
     Free,
 }
@@ -27,6 +31,7 @@ impl fmt::Display for UserExternalIDP {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize)]
 pub struct UserPayload {
     pub external_idp_id: f64,
@@ -39,8 +44,7 @@ impl fmt::Display for UserPayload {
         write!(
             f,
             "external_idp_id: {}, external_idp: {}",
-            self.external_idp_id,
-            self.external_idp.to_string()
+            self.external_idp_id, self.external_idp
         )
     }
 }
@@ -65,6 +69,20 @@ pub struct UserRequestUpdate {
     pub sign_id: i32,
 }
 
+// this struct will use to receive the details a user signing in for the first time still has to provide
+#[derive(Serialize, Deserialize)]
+pub struct OnboardingRequest {
+    pub sign_id: i32,
+    pub display_name: Option<String>,
+}
+
+// this struct tells the client whether the user has finished onboarding
+#[derive(Serialize, Deserialize)]
+pub struct OnboardingState {
+    pub complete: bool,
+    pub missing: Vec<String>,
+}
+
 // this struct will be used to represent database record
 #[derive(Serialize, Deserialize, FromRow, Debug)]
 pub struct User {
@@ -73,13 +91,14 @@ pub struct User {
     pub external_idp_id: String,
     pub external_idp: UserExternalIDP,
     pub display_name: Option<String>,
-    pub sign_id: i32,
+    // not set until a user created on first login has finished onboarding
+    pub sign_id: Option<i32>,
     pub is_internal: Option<bool>,
 }
 
 impl fmt::Display for User {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "User id: {}, email: {:?}, external_idp_id: {:?}, external_idp: {}, display_name: {:?}, sign_id: {}", self.id, self.email, self.external_idp_id, self.external_idp, self.display_name, self.sign_id)
+        write!(f, "User id: {}, email: {:?}, external_idp_id: {:?}, external_idp: {}, display_name: {:?}, sign_id: {:?}", self.id, self.email, self.external_idp_id, self.external_idp, self.display_name, self.sign_id)
     }
 }
 
@@ -149,10 +168,10 @@ impl User {
                     is_internal
                 from
                     users s
-                join sign s2 on
+                left join sign s2 on
                     s2.id = s.sign_id
                 where
-                    id = $1",
+                    s.id = $1",
         )
         .bind(user.user.id)
         .fetch_optional(pool)
@@ -169,8 +188,8 @@ impl User {
         // })
     }
 
-    // find by google, facebook, twitter user ID
-    pub async fn find_by_idp_id(id: &String, pool: &PgPool) -> Result<Option<User>> {
+    // find by our in house UUID, e.g. the subject of our own session tokens
+    pub async fn find_by_uuid(id: Uuid, pool: &PgPool) -> Result<Option<User>> {
         let user = sqlx::query_as::<_, User>(
             "select
                     s.id,
@@ -182,15 +201,44 @@ impl User {
                     is_internal
                 from
                     users s
-                join sign s2 on
-                    s2.id = s.sign_id
                 where
-                    s.external_idp_id = $1",
+                    s.id = $1",
         )
         .bind(id)
         .fetch_optional(pool)
         .await?;
 
+        Ok(user)
+    }
+
+    // find by google, facebook, twitter user ID, through any of the identities linked to the user
+    pub async fn find_by_idp_id(
+        external_idp: &UserExternalIDP,
+        external_idp_id: &str,
+        pool: &PgPool,
+    ) -> Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>(
+            "select
+                    s.id,
+                    s.external_idp_id ,
+                    s.external_idp ,
+                    s.display_name ,
+                    s.sign_id,
+                    s.email,
+                    is_internal
+                from
+                    user_identities i
+                join users s on
+                    s.id = i.user_id
+                where
+                    i.provider = $1
+                    and i.subject = $2",
+        )
+        .bind(external_idp)
+        .bind(external_idp_id)
+        .fetch_optional(pool)
+        .await?;
+
         Ok(user)
         // Ok(User {
         //     id: rec.id,
@@ -214,12 +262,12 @@ impl User {
                     is_internal
                 from
                     users s
-                join sign s2 on
+                left join sign s2 on
                     s2.id = s.sign_id
                 where
                     s.email = $1",
         )
-        .bind(&email)
+        .bind(email)
         .fetch_optional(pool)
         .await?;
 
@@ -289,6 +337,157 @@ impl User {
         Ok(user)
     }
 
+    // flag a user as internal staff, e.g. after signing in through the company's Google Workspace domain
+    pub async fn mark_internal(id: Uuid, pool: &PgPool) -> Result<User> {
+        let mut tx = pool.begin().await?;
+        let user = sqlx::query("UPDATE USERS set IS_INTERNAL = true where ID = $1
+                                    RETURNING ID, EXTERNAL_IDP, EXTERNAL_IDP_ID, DISPLAY_NAME, SIGN_ID, EMAIL, IS_INTERNAL")
+            .bind(id)
+            .map(|row: PgRow| {
+                User {
+                    id: row.get(0),
+                    external_idp: row.get(1),
+                    external_idp_id: row.get(2),
+                    display_name: row.get(3),
+                    sign_id: row.get(4),
+                    email: row.get(5),
+                    is_internal: row.get(6)
+                }
+            })
+            .fetch_one(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    // serialise the transactions creating or linking the identity, until the transaction ends
+    pub async fn lock_identity(
+        external_idp: &UserExternalIDP,
+        external_idp_id: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<()> {
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1::text || '-' || $2))")
+            .bind(external_idp)
+            .bind(external_idp_id)
+            .execute(tx)
+            .await?;
+        Ok(())
+    }
+
+    // create the user signing in for the first time with a verified token, along with their first identity.
+    // Concurrent first requests of the same account are serialised on a lock of the identity; the ones
+    // waiting find the identity linked once they get the lock, and return its user instead.
+    pub async fn provision(
+        external_idp: UserExternalIDP,
+        external_idp_id: &str,
+        email: Option<&str>,
+        display_name: Option<&str>,
+        is_internal: bool,
+        pool: &PgPool,
+    ) -> Result<User> {
+        let mut tx = pool.begin().await?;
+        User::lock_identity(&external_idp, external_idp_id, &mut tx).await?;
+        let linked_user_id =
+            sqlx::query("SELECT USER_ID FROM USER_IDENTITIES where PROVIDER = $1 and SUBJECT = $2")
+                .bind(&external_idp)
+                .bind(external_idp_id)
+                .map(|row: PgRow| row.get::<Uuid, _>(0))
+                .fetch_optional(&mut tx)
+                .await?;
+        if let Some(user_id) = linked_user_id {
+            tx.commit().await?;
+            return User::find_by_uuid(user_id, pool).await?.ok_or_else(|| {
+                anyhow::anyhow!("User {} vanished while being provisioned", external_idp_id)
+            });
+        }
+
+        // an email already taken by another account, possibly one being provisioned concurrently
+        // through another identity, is left out rather than failing the login
+        let mut user = None;
+        for email in [email, None].iter() {
+            user = sqlx::query("INSERT INTO USERS (EXTERNAL_IDP, EXTERNAL_IDP_ID, EMAIL, DISPLAY_NAME, IS_INTERNAL)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (EMAIL) DO NOTHING
+                    RETURNING ID, EXTERNAL_IDP, EXTERNAL_IDP_ID, DISPLAY_NAME, SIGN_ID, EMAIL, IS_INTERNAL")
+                .bind(&external_idp)
+                .bind(external_idp_id)
+                .bind(email)
+                .bind(display_name)
+                .bind(is_internal)
+                .map(|row: PgRow| {
+                    User {
+                        id: row.get(0),
+                        external_idp: row.get(1),
+                        external_idp_id: row.get(2),
+                        display_name: row.get(3),
+                        sign_id: row.get(4),
+                        email: row.get(5),
+                        is_internal: row.get(6)
+                    }
+                })
+                .fetch_optional(&mut tx)
+                .await?;
+            if user.is_some() {
+                break;
+            }
+        }
+        // without an email, the insert has nothing to conflict on
+        let user =
+            user.ok_or_else(|| anyhow::anyhow!("User {} was not created", external_idp_id))?;
+
+        sqlx::query("INSERT INTO USER_IDENTITIES (PROVIDER, SUBJECT, USER_ID) VALUES ($1, $2, $3)")
+            .bind(&external_idp)
+            .bind(external_idp_id)
+            .bind(user.id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    pub fn onboarding_state(&self) -> OnboardingState {
+        let mut missing = vec![];
+        if self.sign_id.is_none() {
+            missing.push("sign_id".to_string());
+        }
+        OnboardingState {
+            complete: missing.is_empty(),
+            missing,
+        }
+    }
+
+    // set the details missing from a user created on first login. Returns None once onboarding is complete.
+    pub async fn complete_onboarding(
+        user: AuthenticatedUser,
+        onboarding: OnboardingRequest,
+        pool: &PgPool,
+    ) -> Result<Option<User>> {
+        let mut tx = pool.begin().await?;
+        let user = sqlx::query("UPDATE USERS set SIGN_ID = $1, DISPLAY_NAME = COALESCE($2, DISPLAY_NAME) where ID = $3 and SIGN_ID is null
+                                    RETURNING ID, EXTERNAL_IDP, EXTERNAL_IDP_ID, DISPLAY_NAME, SIGN_ID, EMAIL, IS_INTERNAL")
+            .bind(onboarding.sign_id)
+            .bind(onboarding.display_name)
+            .bind(user.user.id)
+            .map(|row: PgRow| {
+                User {
+                    id: row.get(0),
+                    external_idp: row.get(1),
+                    external_idp_id: row.get(2),
+                    display_name: row.get(3),
+                    sign_id: row.get(4),
+                    email: row.get(5),
+                    is_internal: row.get(6)
+                }
+            })
+            .fetch_optional(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
     pub async fn delete(user: AuthenticatedUser, pool: &PgPool) -> Result<u64> {
         // TODO:: delete from REDIS as well
 
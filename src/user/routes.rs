@@ -1,6 +1,6 @@
 use crate::{
     auth::AuthenticatedUser,
-    user::{User, UserRequest, UserRequestUpdate},
+    user::{OnboardingRequest, User, UserRequest, UserRequestUpdate},
 };
 use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
 use sqlx::PgPool;
@@ -14,6 +14,32 @@ async fn find_all(user: AuthenticatedUser, db_pool: web::Data<PgPool>) -> impl R
     }
 }
 
+// what a user created on first login still has to provide, e.g. their sign
+#[get("/user/onboarding")]
+async fn onboarding_state(user: AuthenticatedUser) -> impl Responder {
+    HttpResponse::Ok().json(user.user.onboarding_state())
+}
+
+#[post("/user/onboarding")]
+async fn complete_onboarding(
+    user: AuthenticatedUser,
+    onboarding: web::Json<OnboardingRequest>,
+    db_pool: web::Data<PgPool>,
+) -> impl Responder {
+    let result = User::complete_onboarding(user, onboarding.into_inner(), db_pool.get_ref()).await;
+    match result {
+        Ok(Some(user)) => HttpResponse::Ok().json(user),
+        Ok(None) => HttpResponse::BadRequest().body("Onboarding already completed"),
+        Err(e) => {
+            debug!(
+                "Error occurred in User >> routes.rs complete_onboarding function: \n{}",
+                e
+            );
+            HttpResponse::BadRequest().body("Error while completing onboarding")
+        }
+    }
+}
+
 #[get("/user/{id}")]
 async fn find(
     user: AuthenticatedUser,
@@ -75,6 +101,9 @@ async fn delete(user: AuthenticatedUser, db_pool: web::Data<PgPool>) -> impl Res
 }
 
 pub fn init(cfg: &mut web::ServiceConfig) {
+    // registered before /user/{id}, which would otherwise match them
+    cfg.service(onboarding_state);
+    cfg.service(complete_onboarding);
     cfg.service(find_all);
     cfg.service(find);
     cfg.service(create);
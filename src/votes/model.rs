@@ -102,7 +102,7 @@ impl QualityChoiceVote {
     pub async fn delete_all_by_user(user: AuthenticatedUser, pool: &PgPool) -> Result<()> {
         let mut tx = pool.begin().await?;
         sqlx::query("DELETE FROM votes WHERE user_id = $1")
-            .bind(&user.user.id)
+            .bind(user.user.id)
             .execute(&mut tx)
             .await?;
 
@@ -119,9 +119,9 @@ impl QualityChoiceVote {
     ) -> Result<(), AppError> {
         let mut tx = pool.begin().await?;
         sqlx::query("DELETE FROM votes WHERE user_id = $1 and quality_id = $2 and choice_id = $3")
-            .bind(&user.user.id)
-            .bind(&q_id)
-            .bind(&c_id)
+            .bind(user.user.id)
+            .bind(q_id)
+            .bind(c_id)
             .execute(&mut tx)
             .await?;
 
@@ -139,7 +139,7 @@ impl QualityChoiceVote {
         // first check if the incoming choice id (c_id) is a valid choice for the incoming quality
         let valid_choices: Vec<ValidChoices> =
             sqlx::query_as(r#"select id from choice where quality_id = $1"#)
-                .bind(&q_id)
+                .bind(q_id)
                 .fetch_all(pool)
                 .await?;
 
@@ -158,9 +158,9 @@ impl QualityChoiceVote {
                     quality_id = $2
                     and user_id = $3",
         )
-        .bind(&c_id)
-        .bind(&q_id)
-        .bind(&user.user.id)
+        .bind(c_id)
+        .bind(q_id)
+        .bind(user.user.id)
         .fetch_optional(&mut tx)
         .await?;
 
@@ -170,6 +170,7 @@ impl QualityChoiceVote {
     }
 
     // user wants to cast a vote
+    #[allow(dead_code)]
     pub async fn create(
         user: AuthenticatedUser,
         q_id: i32,
@@ -179,7 +180,7 @@ impl QualityChoiceVote {
         // first check if the incoming choice id (c_id) is a valid choice for the incoming quality
         let valid_choices: Vec<ValidChoices> =
             sqlx::query_as(r#"select id from choice where quality_id = $1"#)
-                .bind(&q_id)
+                .bind(q_id)
                 .fetch_all(pool)
                 .await?;
 
@@ -192,13 +193,13 @@ impl QualityChoiceVote {
         let valid_choices: Vec<ValidChoices> = sqlx::query_as(
             r#"select id from votes WHERE user_id = $1 and quality_id = $2 and choice_id = $3"#,
         )
-        .bind(&user.user.id)
-        .bind(&q_id)
-        .bind(&c_id)
+        .bind(user.user.id)
+        .bind(q_id)
+        .bind(c_id)
         .fetch_all(pool)
         .await?;
 
-        if valid_choices.len() > 0 {
+        if !valid_choices.is_empty() {
             return Err(AppError::ALREADY_VOTED.into());
         }
 
@@ -214,9 +215,9 @@ impl QualityChoiceVote {
                 VALUES 
                     ($1, $2, $3)",
         )
-        .bind(&q_id)
-        .bind(&c_id)
-        .bind(&user.user.id)
+        .bind(q_id)
+        .bind(c_id)
+        .bind(user.user.id)
         .fetch_optional(&mut tx)
         .await?;
 